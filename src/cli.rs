@@ -0,0 +1,320 @@
+use std::{ops::RangeInclusive, path::PathBuf};
+
+use crate::game::{
+    mode::GameMode,
+    settings::{BOARD_HEIGHT_RANGE, BOARD_WIDTH_RANGE},
+    theme::Theme,
+};
+
+pub const LEVEL_RANGE: RangeInclusive<usize> = 0..=29;
+
+pub enum Command {
+    /// starts a game right away, skipping the title screen
+    Play,
+    /// watches a saved replay
+    Replay(PathBuf),
+    /// prints the high scores
+    Scores,
+    /// watches the bot play
+    Bot,
+}
+
+impl Command {
+    const NAMES: [&'static str; 4] = ["play", "replay", "scores", "bot"];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Command::Play => "play",
+            Command::Replay(_) => "replay",
+            Command::Scores => "scores",
+            Command::Bot => "bot",
+        }
+    }
+}
+
+/// parsed command line, see `usage`
+#[derive(Default)]
+pub struct Cli {
+    /// `None` shows the title screen
+    pub command: Option<Command>,
+    pub help: bool,
+    pub version: bool,
+    pub safe: bool,
+    pub no_color: bool,
+    pub mode: Option<GameMode>,
+    pub seed: Option<u64>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub level: Option<usize>,
+    pub config: Option<PathBuf>,
+    pub theme: Option<Theme>,
+    pub record: Option<PathBuf>,
+    pub puzzle: Option<PathBuf>,
+}
+
+pub fn usage() -> String {
+    let bin = env!("CARGO_PKG_NAME");
+    let modes = GameMode::ALL.map(|m| m.name().to_lowercase()).join(", ");
+    let themes = Theme::ALL.map(|t| t.name()).join(", ");
+    format!(
+        "\
+Usage: {bin} [COMMAND] [OPTIONS]
+
+Commands:
+  play             start a game right away
+  replay <FILE>    watch a replay
+  scores           print the high scores
+  bot              watch the bot play
+  (none)           show the title screen
+
+Options:
+  -m, --mode <MODE>     game mode ({modes})
+      --seed <SEED>     seed of the pieces, every game of the session uses it
+      --width <N>       board width in blocks ({}-{}, 10 by default)
+      --height <N>      board height in blocks ({}-{}, 20 by default)
+      --level <N>       starting level ({}-{})
+      --config <FILE>   config file to read and save the settings to
+      --theme <THEME>   color theme for this session ({themes})
+      --replay <FILE>   same as the replay command
+      --record <FILE>   write the replay of each finished game to FILE
+      --puzzle <FILE>   play the puzzle of FILE (puzzle mode), the board is sized to fit it
+      --no-color        draw without colors (also set by NO_COLOR)
+  -s, --safe            give the terminal back even if the game crashes
+  -h, --help            print this help
+  -V, --version         print the version
+",
+        BOARD_WIDTH_RANGE.start(),
+        BOARD_WIDTH_RANGE.end(),
+        BOARD_HEIGHT_RANGE.start(),
+        BOARD_HEIGHT_RANGE.end(),
+        LEVEL_RANGE.start(),
+        LEVEL_RANGE.end(),
+    )
+}
+
+/// parses a number option, `range` being its allowed values
+fn parse_number<T>(flag: &str, value: &str, range: Option<RangeInclusive<T>>) -> Result<T, String>
+where
+    T: std::str::FromStr + PartialOrd + std::fmt::Display,
+{
+    let n = value
+        .parse::<T>()
+        .map_err(|_| format!("invalid value `{value}` for `{flag}`: expected a number"))?;
+    match range {
+        Some(range) if !range.contains(&n) => Err(format!(
+            "invalid value `{value}` for `{flag}`: must be between {} and {}",
+            range.start(),
+            range.end()
+        )),
+        _ => Ok(n),
+    }
+}
+
+impl Cli {
+    /// parses the arguments (program name excluded), the error is meant to be shown to the user
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut cli = Cli::default();
+        let mut replay_flag = None;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // `--flag=value` is the same as `--flag value`
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or(format!("`{flag}` needs a value"))
+            };
+
+            match flag {
+                "-h" | "--help" => cli.help = true,
+                "-V" | "--version" => cli.version = true,
+                "-s" | "--safe" => cli.safe = true,
+                "--no-color" => cli.no_color = true,
+                "-m" | "--mode" => {
+                    let name = value()?;
+                    cli.mode = Some(GameMode::from_name(&name).ok_or(format!(
+                        "unknown mode `{name}`, expected one of {}",
+                        GameMode::ALL.map(|m| m.name().to_lowercase()).join(", ")
+                    ))?)
+                }
+                "--seed" => cli.seed = Some(parse_number(flag, &value()?, None)?),
+                "--width" => {
+                    cli.width = Some(parse_number(flag, &value()?, Some(BOARD_WIDTH_RANGE))?)
+                }
+                "--height" => {
+                    cli.height = Some(parse_number(flag, &value()?, Some(BOARD_HEIGHT_RANGE))?)
+                }
+                "--level" => cli.level = Some(parse_number(flag, &value()?, Some(LEVEL_RANGE))?),
+                "--config" => cli.config = Some(PathBuf::from(value()?)),
+                "--theme" => {
+                    let name = value()?;
+                    cli.theme = Some(Theme::from_name(&name).ok_or(format!(
+                        "unknown theme `{name}`, expected one of {}",
+                        Theme::ALL.map(|t| t.name()).join(", ")
+                    ))?)
+                }
+                "--replay" => replay_flag = Some(PathBuf::from(value()?)),
+                "--record" => cli.record = Some(PathBuf::from(value()?)),
+                "--puzzle" => cli.puzzle = Some(PathBuf::from(value()?)),
+                flag if flag.starts_with('-') && flag.len() > 1 => {
+                    return Err(format!("unknown argument `{arg}`"))
+                }
+                name => {
+                    if let Some(command) = &cli.command {
+                        return Err(format!(
+                            "unexpected argument `{name}` after the `{}` command",
+                            command.name()
+                        ));
+                    }
+                    cli.command = Some(match name {
+                        "play" => Command::Play,
+                        "replay" => Command::Replay(PathBuf::from(
+                            args.next().ok_or("`replay` needs a replay file")?,
+                        )),
+                        "scores" => Command::Scores,
+                        "bot" => Command::Bot,
+                        _ => {
+                            return Err(format!(
+                                "unknown command `{name}`, expected one of {}",
+                                Command::NAMES.join(", ")
+                            ))
+                        }
+                    });
+                }
+            }
+
+            if inline.is_some() && !Self::takes_value(flag) {
+                return Err(format!("`{flag}` doesn't take a value"));
+            }
+        }
+
+        if let Some(path) = replay_flag {
+            cli.command = match cli.command {
+                None | Some(Command::Replay(_)) => Some(Command::Replay(path)),
+                Some(command) => {
+                    return Err(format!(
+                        "`--replay` can't be used with the `{}` command",
+                        command.name()
+                    ))
+                }
+            }
+        }
+        if matches!(cli.command, Some(Command::Replay(_))) {
+            let flags = [
+                ("--mode", cli.mode.is_some()),
+                ("--record", cli.record.is_some()),
+                ("--seed", cli.seed.is_some()),
+                ("--level", cli.level.is_some()),
+                ("--width", cli.width.is_some()),
+                ("--height", cli.height.is_some()),
+                ("--puzzle", cli.puzzle.is_some()),
+            ];
+            if let Some((flag, _)) = flags.iter().find(|(_, given)| *given) {
+                return Err(format!("`{flag}` can't be used when watching a replay"));
+            }
+        }
+        if cli.puzzle.is_some() {
+            match cli.mode {
+                None | Some(GameMode::Puzzle) => cli.mode = Some(GameMode::Puzzle),
+                Some(mode) => {
+                    return Err(format!(
+                        "`--puzzle` can't be used with the {} mode",
+                        mode.name().to_lowercase()
+                    ))
+                }
+            }
+            if cli.width.is_some() {
+                return Err("`--width` can't be used with `--puzzle`".to_string());
+            }
+        }
+        Ok(cli)
+    }
+
+    fn takes_value(flag: &str) -> bool {
+        matches!(
+            flag,
+            "--mode"
+                | "--seed"
+                | "--width"
+                | "--height"
+                | "--level"
+                | "--config"
+                | "--theme"
+                | "--replay"
+                | "--record"
+                | "--puzzle"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Cli, String> {
+        Cli::parse(args.split_whitespace().map(String::from))
+    }
+
+    fn error(args: &str) -> String {
+        match parse(args) {
+            Ok(_) => panic!("`{args}` was accepted"),
+            Err(why) => why,
+        }
+    }
+
+    #[test]
+    fn parses_commands_and_options() {
+        let cli = parse("play --mode marathon --seed=7 --level 3 --theme mono").unwrap();
+        assert!(matches!(cli.command, Some(Command::Play)));
+        assert!(cli.mode == Some(GameMode::Marathon));
+        assert_eq!(cli.seed, Some(7));
+        assert_eq!(cli.level, Some(3));
+        assert!(cli.theme == Some(Theme::Mono));
+
+        let cli = parse("--replay game.replay").unwrap();
+        assert!(
+            matches!(cli.command, Some(Command::Replay(path)) if path.as_os_str() == "game.replay")
+        );
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        assert_eq!(
+            error("--level 30"),
+            "invalid value `30` for `--level`: must be between 0 and 29"
+        );
+        assert_eq!(error("--seed"), "`--seed` needs a value");
+        assert_eq!(error("--safe=1"), "`--safe` doesn't take a value");
+        assert_eq!(error("--fast"), "unknown argument `--fast`");
+        assert_eq!(
+            error("play scores"),
+            "unexpected argument `scores` after the `play` command"
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_options() {
+        assert_eq!(
+            error("play --replay game.replay"),
+            "`--replay` can't be used with the `play` command"
+        );
+        assert_eq!(
+            error("replay game.replay --record out.replay"),
+            "`--record` can't be used when watching a replay"
+        );
+        assert_eq!(
+            error("--puzzle tsd.toml --mode marathon"),
+            "`--puzzle` can't be used with the marathon mode"
+        );
+        assert_eq!(
+            error("--puzzle tsd.toml --width 6"),
+            "`--width` can't be used with `--puzzle`"
+        );
+        assert!(parse("--puzzle tsd.toml").unwrap().mode == Some(GameMode::Puzzle));
+    }
+}
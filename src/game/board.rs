@@ -0,0 +1,356 @@
+use nanorand::{Rng, WyRand};
+
+use crate::utils::Ivec2;
+
+use super::types::TetrominoeType;
+
+/// rows above the visible field, pieces can be moved and locked there but they aren't drawn
+pub const HIDDEN_ROWS: usize = 20;
+
+/// how a game was lost
+#[derive(Clone, Copy, PartialEq)]
+pub enum TopOut {
+    /// the new piece overlaps the stack where it spawns
+    Block,
+    /// a piece locked fully above the visible field
+    Lock,
+    /// blocks were left above the visible field (only with the partial lock out rule)
+    PartialLock,
+    /// rising garbage pushed blocks out of the board or left the falling piece no room
+    Garbage,
+}
+
+impl TopOut {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TopOut::Block => "BLOCK OUT",
+            TopOut::Lock => "LOCK OUT",
+            TopOut::PartialLock => "PARTIAL LOCK OUT",
+            TopOut::Garbage => "TOP OUT",
+        }
+    }
+}
+
+/// a locked block
+#[derive(Clone, Copy, PartialEq)]
+pub enum Block {
+    /// part of a piece of this type (which gives its color)
+    Piece(TetrominoeType),
+    /// part of a garbage row
+    Garbage,
+}
+
+/// the playfield in blocks, independent of how it's drawn.
+/// Row 0 is the top of the hidden rows, the visible field is made of the `height` last rows
+/// (`height` given to `new`)
+#[derive(Clone)]
+pub struct Board {
+    pub width: usize,
+    /// locked blocks, `None` for empty cells
+    rows: Vec<Vec<Option<Block>>>,
+    /// number of garbage rows left at the bottom. Pieces can't get below them and a garbage row
+    /// only has one hole, so they stay at the bottom until cleared
+    pub garbage: usize,
+    /// column of the hole of the last garbage row added
+    last_hole: Option<usize>,
+}
+
+impl Board {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            rows: vec![vec![None; width]; height + HIDDEN_ROWS],
+            garbage: 0,
+            last_hole: None,
+        }
+    }
+
+    /// whether `pos` is inside the board (hidden rows included) and empty
+    pub fn is_free(&self, pos: Ivec2) -> bool {
+        if pos.x < 0 || pos.y < 0 {
+            return false;
+        }
+        self.rows
+            .get(pos.y as usize)
+            .and_then(|row| row.get(pos.x as usize))
+            .is_some_and(|cell| cell.is_none())
+    }
+
+    /// whether a piece made of `blocks` can be there
+    pub fn fits(&self, blocks: &[Ivec2]) -> bool {
+        blocks.iter().all(|b| self.is_free(*b))
+    }
+
+    /// number of rows `blocks` can fall before landing on the stack or the floor
+    pub fn drop_distance(&self, blocks: &[Ivec2]) -> usize {
+        let mut distance = 0;
+        loop {
+            let offset = Ivec2::new(0, distance as isize + 1);
+            if !blocks.iter().all(|b| self.is_free(*b + offset)) {
+                return distance;
+            }
+            distance += 1;
+        }
+    }
+
+    /// adds `blocks` to the stack
+    pub fn lock(&mut self, blocks: &[Ivec2], ttype: TetrominoeType) {
+        for b in blocks {
+            if let Some(cell) = self
+                .rows
+                .get_mut(b.y as usize)
+                .and_then(|row| row.get_mut(b.x as usize))
+            {
+                *cell = Some(Block::Piece(ttype))
+            }
+        }
+    }
+
+    /// indices of the full rows, top to bottom
+    pub fn full_rows(&self) -> Vec<usize> {
+        (0..self.rows.len())
+            .filter(|y| self.rows[*y].iter().all(|cell| cell.is_some()))
+            .collect()
+    }
+
+    /// removes `rows` (sorted top to bottom) and collapses the stack above them
+    pub fn remove_rows(&mut self, rows: &[usize]) {
+        self.garbage = self.garbage_left(rows);
+        for y in rows {
+            self.rows.remove(*y);
+            self.rows.insert(0, vec![None; self.width]);
+        }
+    }
+
+    /// removes the full rows and collapses the stack above them, returns the number of cleared rows
+    pub fn clear_full_rows(&mut self) -> usize {
+        let rows = self.full_rows();
+        self.remove_rows(&rows);
+        rows.len()
+    }
+
+    /// garbage rows left once `rows` are removed
+    pub fn garbage_left(&self, rows: &[usize]) -> usize {
+        let first_garbage = self.rows.len() - self.garbage;
+        self.garbage - rows.iter().filter(|y| **y >= first_garbage).count()
+    }
+
+    /// pushes the stack up by `rows` garbage rows, each full but for one hole. From a row to the
+    /// next, the hole moves to another random column with a `messiness` percent chance.
+    /// Returns whether blocks got pushed out of the top of the board
+    pub fn add_garbage(&mut self, rows: usize, messiness: usize, rng: &mut WyRand) -> bool {
+        let mut pushed_out = false;
+        for _ in 0..rows {
+            let hole = match self.last_hole {
+                Some(hole) if rng.generate_range(0_usize..100) >= messiness => hole,
+                // any other column
+                Some(hole) => (hole + 1 + rng.generate_range(0..self.width - 1)) % self.width,
+                None => rng.generate_range(0..self.width),
+            };
+            let mut row = vec![Some(Block::Garbage); self.width];
+            row[hole] = None;
+            pushed_out |= self.rows.remove(0).iter().any(|cell| cell.is_some());
+            self.rows.push(row);
+            self.garbage += 1;
+            self.last_hole = Some(hole);
+        }
+        pushed_out
+    }
+
+    /// empties the board, garbage rows included
+    pub fn clear(&mut self) {
+        for row in self.rows.iter_mut() {
+            row.fill(None);
+        }
+        self.garbage = 0;
+        self.last_hole = None;
+    }
+
+    /// replaces the bottom rows with `rows` (top to bottom), the starting board of a puzzle
+    pub fn set_bottom_rows(&mut self, rows: &[Vec<Option<TetrominoeType>>]) {
+        let first = self.rows.len() - rows.len();
+        for (row, blocks) in self.rows[first..].iter_mut().zip(rows) {
+            *row = blocks.iter().map(|b| b.map(Block::Piece)).collect();
+        }
+    }
+
+    /// whether every block of the board is in `rows`, a perfect clear once they're removed
+    pub fn is_empty_but(&self, rows: &[usize]) -> bool {
+        self.rows
+            .iter()
+            .enumerate()
+            .all(|(y, row)| rows.contains(&y) || row.iter().all(|cell| cell.is_none()))
+    }
+
+    /// whether `pos` is in the hidden rows
+    pub fn is_hidden(pos: Ivec2) -> bool {
+        pos.y < HIDDEN_ROWS as isize
+    }
+
+    /// whether some locked blocks are in the hidden rows
+    pub fn has_hidden_blocks(&self) -> bool {
+        self.rows[..HIDDEN_ROWS]
+            .iter()
+            .any(|row| row.iter().any(|cell| cell.is_some()))
+    }
+
+    /// the rows shown to the player, top to bottom
+    pub fn visible_rows(&self) -> &[Vec<Option<Block>>] {
+        &self.rows[HIDDEN_ROWS..]
+    }
+
+    /// the locked cells of a column, top to bottom (hidden rows included)
+    pub fn column(&self, x: usize) -> impl Iterator<Item = Option<Block>> + '_ {
+        self.rows.iter().map(move |row| row[x])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the bottom `n` rows as text, `#` for blocks
+    fn bottom(board: &Board, n: usize) -> Vec<String> {
+        let rows = board.visible_rows();
+        rows[rows.len() - n..]
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|b| if b.is_some() { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn row(y: usize, xs: &[isize]) -> Vec<Ivec2> {
+        xs.iter().map(|x| Ivec2::new(*x, y as isize)).collect()
+    }
+
+    #[test]
+    fn clearing_rows_collapses_the_stack() {
+        let mut board = Board::new(4, 4);
+        let y = HIDDEN_ROWS;
+        board.lock(&row(y, &[0]), TetrominoeType::I);
+        board.lock(&row(y + 1, &[0, 1, 2, 3]), TetrominoeType::I);
+        board.lock(&row(y + 2, &[1]), TetrominoeType::I);
+        board.lock(&row(y + 3, &[0, 1, 2, 3]), TetrominoeType::I);
+
+        assert_eq!(board.clear_full_rows(), 2);
+        assert_eq!(bottom(&board, 4), ["....", "....", "#...", ".#.."]);
+        assert_eq!(board.clear_full_rows(), 0);
+    }
+
+    #[test]
+    fn full_rows_stay_until_removed() {
+        let mut board = Board::new(3, 3);
+        let y = HIDDEN_ROWS;
+        board.lock(&row(y + 1, &[0, 1, 2]), TetrominoeType::I);
+        board.lock(&row(y + 2, &[0, 2]), TetrominoeType::I);
+
+        assert_eq!(board.full_rows(), [y + 1]);
+        assert_eq!(bottom(&board, 2), ["###", "#.#"]);
+        board.remove_rows(&[y + 1]);
+        assert_eq!(bottom(&board, 3), ["...", "...", "#.#"]);
+    }
+
+    #[test]
+    fn pieces_land_on_the_stack() {
+        let mut board = Board::new(4, 4);
+        let piece = row(0, &[0, 1]);
+        assert_eq!(board.drop_distance(&piece), HIDDEN_ROWS + 3);
+        board.lock(&row(HIDDEN_ROWS + 2, &[1]), TetrominoeType::I);
+        assert_eq!(board.drop_distance(&piece), HIDDEN_ROWS + 1);
+        assert!(!board.fits(&row(HIDDEN_ROWS + 2, &[0, 1])));
+        assert!(!board.fits(&row(0, &[-1])));
+        assert!(!board.fits(&row(0, &[4])));
+    }
+
+    #[test]
+    fn blocks_above_the_field_are_hidden() {
+        let mut board = Board::new(4, 4);
+        assert!(Board::is_hidden(Ivec2::new(0, HIDDEN_ROWS as isize - 1)));
+        assert!(!Board::is_hidden(Ivec2::new(0, HIDDEN_ROWS as isize)));
+
+        board.lock(&row(HIDDEN_ROWS, &[0]), TetrominoeType::I);
+        assert!(!board.has_hidden_blocks());
+        board.lock(&row(HIDDEN_ROWS - 1, &[0]), TetrominoeType::I);
+        assert!(board.has_hidden_blocks());
+    }
+
+    #[test]
+    fn garbage_rises_from_the_bottom() {
+        let mut rng = WyRand::new_seed(1);
+        let mut board = Board::new(4, 4);
+        board.lock(&row(HIDDEN_ROWS + 3, &[0]), TetrominoeType::I);
+        assert!(!board.add_garbage(3, 0, &mut rng));
+        assert_eq!(board.garbage, 3);
+
+        let rows = bottom(&board, 4);
+        assert_eq!(rows[0], "#...");
+        // one hole per row, in the same column without messiness
+        let holes = rows[1..]
+            .iter()
+            .map(|row| row.find('.').unwrap())
+            .collect::<Vec<_>>();
+        assert!(rows[1..].iter().all(|row| row.matches('.').count() == 1));
+        assert!(holes.iter().all(|hole| *hole == holes[0]));
+
+        // with full messiness every hole moves
+        board.add_garbage(3, 100, &mut rng);
+        let holes = bottom(&board, 4)
+            .iter()
+            .map(|row| row.find('.').unwrap())
+            .collect::<Vec<_>>();
+        assert!(holes.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn garbage_pushes_blocks_out_of_the_top() {
+        let mut rng = WyRand::new_seed(1);
+        let mut board = Board::new(4, 4);
+        board.lock(&[Ivec2::new(0, 0)], TetrominoeType::O);
+        assert!(board.add_garbage(1, 0, &mut rng));
+        assert!(!board.add_garbage(1, 0, &mut rng));
+    }
+
+    #[test]
+    fn clearing_a_garbage_row_counts_it_down() {
+        let mut rng = WyRand::new_seed(1);
+        let mut board = Board::new(4, 4);
+        board.add_garbage(2, 0, &mut rng);
+        let hole = bottom(&board, 1)[0].find('.').unwrap() as isize;
+        board.lock(&row(HIDDEN_ROWS + 3, &[hole]), TetrominoeType::I);
+        let full = board.full_rows();
+        assert_eq!(board.garbage_left(&full), 1);
+        // still counted until the row is gone
+        assert_eq!(board.garbage, 2);
+        board.remove_rows(&full);
+        assert_eq!(board.garbage, 1);
+    }
+
+    #[test]
+    fn clearing_empties_the_stack_and_its_garbage() {
+        let mut rng = WyRand::new_seed(1);
+        let mut board = Board::new(4, 4);
+        board.add_garbage(2, 0, &mut rng);
+        board.lock(&[Ivec2::new(0, 0)], TetrominoeType::O);
+        board.clear();
+        assert_eq!(board.garbage, 0);
+        assert!(!board.has_hidden_blocks());
+        assert_eq!(bottom(&board, 4), vec!["....", "....", "....", "...."]);
+    }
+
+    #[test]
+    fn puzzle_rows_fill_the_bottom() {
+        let mut board = Board::new(4, 4);
+        let (i, o) = (Some(TetrominoeType::I), Some(TetrominoeType::O));
+        board.set_bottom_rows(&[vec![o, None, None, None], vec![i, i, i, None]]);
+        assert_eq!(bottom(&board, 3), ["....", "#...", "###."]);
+
+        let y = HIDDEN_ROWS + 3;
+        assert!(!board.is_empty_but(&[y]));
+        board.lock(&row(y, &[3]), TetrominoeType::I);
+        assert!(!board.is_empty_but(&[y]));
+        assert!(board.is_empty_but(&[y - 1, y]));
+    }
+}
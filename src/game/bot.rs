@@ -0,0 +1,122 @@
+use super::{input::Action, types::Tetrominoe, Control, GameManager};
+
+/// ticks between two moves of the bot, slow enough to be watched
+const MOVE_TICKS: u64 = 3;
+/// weights of the placement score, per cleared line, stack block height, hole and height step
+const LINE_WEIGHT: isize = 76;
+const HEIGHT_WEIGHT: isize = 51;
+const HOLE_WEIGHT: isize = 36;
+const BUMPINESS_WEIGHT: isize = 18;
+
+/// plays by itself: tries every rotation and column of the falling piece and drops it where it
+/// leaves the lowest and flattest stack without holes
+#[derive(Default)]
+pub struct Bot {
+    /// moves left to place the falling piece, in order
+    plan: Vec<Action>,
+    /// `stats.pieces` when the plan was made, a new piece needs a new plan
+    planned_for: Option<usize>,
+}
+
+impl GameManager {
+    /// plays the next move of the bot (if it's time to)
+    pub fn play_bot_move(&mut self) {
+        // nothing to plan for until the next piece spawns
+        if !self.game.piece_in_play() {
+            return;
+        }
+        let pieces = self.game.stats.pieces;
+        if matches!(&self.control, Control::Bot(bot) if bot.planned_for != Some(pieces)) {
+            let plan = self.plan_placement();
+            if let Control::Bot(bot) = &mut self.control {
+                bot.plan = plan;
+                bot.planned_for = Some(pieces);
+            }
+        }
+        if !self.game.tick.is_multiple_of(MOVE_TICKS) {
+            return;
+        }
+
+        let action = match &mut self.control {
+            Control::Bot(bot) if !bot.plan.is_empty() => bot.plan.remove(0),
+            _ => return,
+        };
+        self.perform(action)
+    }
+
+    /// moves bringing the falling piece to its best placement, ending with a hard drop
+    fn plan_placement(&self) -> Vec<Action> {
+        let mut best: Option<(isize, Vec<Action>)> = None;
+        let mut rotated = self.game.tetrominoe.clone();
+
+        for rotations in 0..4 {
+            if rotations > 0 {
+                rotated = rotated.rotated(false);
+                if !self.game.board.fits(&rotated.cells()) {
+                    break;
+                }
+            }
+
+            // slide to the left wall, then try every column on the way to the right one
+            let mut piece = rotated.clone();
+            let mut shift = 0_isize;
+            while self.game.board.fits(&piece.moved(-1, 0).cells()) {
+                piece = piece.moved(-1, 0);
+                shift -= 1;
+            }
+
+            loop {
+                let score = self.placement_score(&piece);
+                if best.as_ref().is_none_or(|(best, _)| score > *best) {
+                    let step = match shift < 0 {
+                        true => Action::MoveLeft,
+                        false => Action::MoveRight,
+                    };
+                    let mut plan = vec![Action::RotateCw; rotations];
+                    plan.extend(vec![step; shift.unsigned_abs()]);
+                    plan.push(Action::HardDrop);
+                    best = Some((score, plan));
+                }
+
+                let right = piece.moved(1, 0);
+                if !self.game.board.fits(&right.cells()) {
+                    break;
+                }
+                piece = right;
+                shift += 1;
+            }
+        }
+
+        best.map_or(vec![Action::HardDrop], |(_, plan)| plan)
+    }
+
+    /// higher is better: cleared lines, then a low and flat stack without holes once `piece` is
+    /// dropped
+    fn placement_score(&self, piece: &Tetrominoe) -> isize {
+        let mut board = self.game.board.clone();
+        let fall = board.drop_distance(&piece.cells());
+        board.lock(&piece.moved(0, fall as isize).cells(), piece.ttype);
+        let lines = board.clear_full_rows() as isize;
+
+        let (mut height, mut holes, mut bumpiness) = (0_isize, 0, 0);
+        let mut previous: Option<isize> = None;
+        for x in 0..board.width {
+            let column = board.column(x).collect::<Vec<_>>();
+            let top = column
+                .iter()
+                .position(|c| c.is_some())
+                .unwrap_or(column.len());
+            let h = (column.len() - top) as isize;
+            holes += column[top..].iter().filter(|c| c.is_none()).count() as isize;
+            if let Some(previous) = previous {
+                bumpiness += (h - previous).abs();
+            }
+            previous = Some(h);
+            height += h;
+        }
+        LINE_WEIGHT * lines
+            - HEIGHT_WEIGHT * height
+            - HOLE_WEIGHT * holes
+            - BUMPINESS_WEIGHT * bumpiness
+    }
+}
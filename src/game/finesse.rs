@@ -0,0 +1,190 @@
+use std::collections::VecDeque;
+
+use super::{
+    board::Board,
+    kicks::{self, KickTable},
+    types::Tetrominoe,
+};
+
+/// searching further than this many inputs is pointless, every placement takes fewer
+const MAX_INPUTS: usize = 6;
+
+/// inputs spent on the falling piece, compared with the fewest possible when it locks
+#[derive(Clone, Default)]
+pub struct PieceInputs {
+    /// the piece as it spawned, `None` before the first one
+    pub start: Option<Tetrominoe>,
+    /// move and rotate key presses (auto-repeat excluded)
+    pub inputs: usize,
+    /// soft dropped pieces can reach places no amount of shifting and turning does, they aren't judged
+    pub soft_dropped: bool,
+}
+
+impl PieceInputs {
+    pub fn new(start: &Tetrominoe) -> Self {
+        Self {
+            start: Some(start.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// number of inputs spent on top of the fewest needed to lock like `locked` did, on a board
+    /// the size of `board` (see `kicks::first_fit` for `kicks_180`), turning by 180° if the rules
+    /// allow it
+    pub fn faults(
+        &self,
+        board: &Board,
+        locked: &Tetrominoe,
+        kicks_180: Option<&KickTable>,
+        rotate_180: bool,
+    ) -> usize {
+        match &self.start {
+            Some(start) if !self.soft_dropped => {
+                let fewest = min_inputs(board, start, locked, kicks_180, rotate_180);
+                self.inputs.saturating_sub(fewest)
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// fewest taps, wall to wall slides (DAS) and rotations bringing `start` over the columns
+/// `target` covers in the same shape, on an empty board the size of `board`
+fn min_inputs(
+    board: &Board,
+    start: &Tetrominoe,
+    target: &Tetrominoe,
+    kicks_180: Option<&KickTable>,
+    rotate_180: bool,
+) -> usize {
+    let board = Board::new(board.width, board.visible_rows().len());
+    let goal = footprint(&board, target);
+
+    let mut seen = vec![(start.pos, start.rotation)];
+    let mut queue = VecDeque::from([(start.clone(), 0)]);
+    while let Some((piece, inputs)) = queue.pop_front() {
+        if footprint(&board, &piece) == goal {
+            return inputs;
+        }
+        if inputs == MAX_INPUTS {
+            continue;
+        }
+        for next in moves(&board, &piece, kicks_180, rotate_180) {
+            if !seen.contains(&(next.pos, next.rotation)) {
+                seen.push((next.pos, next.rotation));
+                queue.push_back((next, inputs + 1));
+            }
+        }
+    }
+    // not reachable without soft dropping
+    usize::MAX
+}
+
+/// where the piece can go with a single input
+fn moves(
+    board: &Board,
+    piece: &Tetrominoe,
+    kicks_180: Option<&KickTable>,
+    rotate_180: bool,
+) -> Vec<Tetrominoe> {
+    let mut moves = vec![];
+    for dx in [-1, 1] {
+        let tap = piece.moved(dx, 0);
+        if !board.fits(&tap.cells()) {
+            continue;
+        }
+        let mut slid = tap.clone();
+        while board.fits(&slid.moved(dx, 0).cells()) {
+            slid = slid.moved(dx, 0);
+        }
+        moves.push(tap);
+        moves.push(slid);
+    }
+    let mut turns = vec![piece.rotated(false), piece.rotated(true)];
+    if rotate_180 {
+        turns.push(piece.rotated_180());
+    }
+    for rotated in turns {
+        if let Some((kicked, _)) = kicks::first_fit(board, piece.rotation, &rotated, kicks_180) {
+            moves.push(kicked);
+        }
+    }
+    moves
+}
+
+/// cells the piece covers once dropped to the floor, which tells apart placements that matter
+/// (the I, S and Z pieces have two orientations giving the same one)
+fn footprint(board: &Board, piece: &Tetrominoe) -> Vec<(isize, isize)> {
+    let fall = board.drop_distance(&piece.cells()) as isize;
+    let mut cells = piece.cells().map(|c| (c.x, c.y + fall)).to_vec();
+    cells.sort();
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::rules::Rotation;
+
+    use super::*;
+    use crate::{
+        game::{board::HIDDEN_ROWS, kicks::Kicks180, types::TetrominoeType},
+        utils::Ivec2,
+    };
+
+    fn spawned(ttype: TetrominoeType) -> Tetrominoe {
+        let mut piece = Tetrominoe::new(ttype, Rotation::Srs);
+        piece.pos = Ivec2::new(3, HIDDEN_ROWS as isize);
+        piece
+    }
+
+    fn faults(start: &Tetrominoe, inputs: usize, locked: &Tetrominoe) -> usize {
+        let pressed = PieceInputs {
+            inputs,
+            ..PieceInputs::new(start)
+        };
+        let kicks_180 = Kicks180::SrsPlus.table(&Default::default());
+        pressed.faults(&Board::new(10, 20), locked, Some(&kicks_180), true)
+    }
+
+    #[test]
+    fn extra_inputs_are_faults() {
+        let t = spawned(TetrominoeType::T);
+        assert_eq!(faults(&t, 1, &t.moved(-1, 0)), 0);
+        // a single slide reaches the wall
+        assert_eq!(faults(&t, 3, &t.moved(-3, 0)), 2);
+        // three quarter turns instead of one
+        assert_eq!(faults(&t, 3, &t.rotated(true)), 2);
+        // two quarter turns instead of a half turn
+        assert_eq!(faults(&t, 2, &t.rotated_180()), 1);
+        assert_eq!(faults(&t, 0, &t), 0);
+    }
+
+    #[test]
+    fn equivalent_orientations_are_the_same_placement() {
+        let s = spawned(TetrominoeType::S);
+        // both vertical S pieces fill the same columns once one is moved over
+        let vertical = s.rotated(false);
+        let other = s.rotated(true).moved(1, 0);
+        assert_eq!(faults(&s, 1, &vertical), 0);
+        assert_eq!(faults(&s, 1, &other), 0);
+    }
+
+    #[test]
+    fn soft_dropped_pieces_are_not_judged() {
+        let t = spawned(TetrominoeType::T);
+        let pressed = PieceInputs {
+            inputs: 9,
+            soft_dropped: true,
+            ..PieceInputs::new(&t)
+        };
+        let kicks_180 = Kicks180::None.table(&Default::default());
+        assert_eq!(
+            pressed.faults(&Board::new(10, 20), &t, Some(&kicks_180), true),
+            0
+        );
+        assert_eq!(
+            PieceInputs::default().faults(&Board::new(10, 20), &t, None, true),
+            0
+        );
+    }
+}
@@ -0,0 +1,98 @@
+pub const GLYPH_WIDTH: usize = 5;
+pub const GLYPH_HEIGHT: usize = 5;
+/// blank pixels between two glyphs
+pub const GLYPH_SPACING: usize = 1;
+
+type Glyph = [&'static str; GLYPH_HEIGHT];
+
+/// bitmap of `c` (`#` = filled pixel), latin letters are case insensitive
+pub fn glyph(c: char) -> Option<Glyph> {
+    Some(match c.to_ascii_uppercase() {
+        ' ' => [".....", ".....", ".....", ".....", "....."],
+        '0' => [".###.", "#..##", "#.#.#", "##..#", ".###."],
+        '1' => ["..#..", ".##..", "..#..", "..#..", ".###."],
+        '2' => ["####.", "....#", ".###.", "#....", "#####"],
+        '3' => ["####.", "....#", ".###.", "....#", "####."],
+        '4' => ["#..#.", "#..#.", "#####", "...#.", "...#."],
+        '5' => ["#####", "#....", "####.", "....#", "####."],
+        '6' => [".###.", "#....", "####.", "#...#", ".###."],
+        '7' => ["#####", "....#", "...#.", "..#..", "..#.."],
+        '8' => [".###.", "#...#", ".###.", "#...#", ".###."],
+        '9' => [".###.", "#...#", ".####", "....#", ".###."],
+        'A' => [".###.", "#...#", "#####", "#...#", "#...#"],
+        'B' => ["####.", "#...#", "####.", "#...#", "####."],
+        'C' => [".####", "#....", "#....", "#....", ".####"],
+        'D' => ["####.", "#...#", "#...#", "#...#", "####."],
+        'E' => ["#####", "#....", "####.", "#....", "#####"],
+        'F' => ["#####", "#....", "####.", "#....", "#...."],
+        'G' => [".####", "#....", "#..##", "#...#", ".###."],
+        'H' => ["#...#", "#...#", "#####", "#...#", "#...#"],
+        'I' => [".###.", "..#..", "..#..", "..#..", ".###."],
+        'J' => ["..###", "...#.", "...#.", "#..#.", ".##.."],
+        'K' => ["#..#.", "#.#..", "##...", "#.#..", "#..#."],
+        'L' => ["#....", "#....", "#....", "#....", "#####"],
+        'M' => ["#...#", "##.##", "#.#.#", "#...#", "#...#"],
+        'N' => ["#...#", "##..#", "#.#.#", "#..##", "#...#"],
+        'O' => [".###.", "#...#", "#...#", "#...#", ".###."],
+        'P' => ["####.", "#...#", "####.", "#....", "#...."],
+        'Q' => [".###.", "#...#", "#.#.#", "#..#.", ".##.#"],
+        'R' => ["####.", "#...#", "####.", "#..#.", "#...#"],
+        'S' => [".####", "#....", ".###.", "....#", "####."],
+        'T' => ["#####", "..#..", "..#..", "..#..", "..#.."],
+        'U' => ["#...#", "#...#", "#...#", "#...#", ".###."],
+        'V' => ["#...#", "#...#", "#...#", ".#.#.", "..#.."],
+        'W' => ["#...#", "#...#", "#.#.#", "##.##", "#...#"],
+        'X' => ["#...#", ".#.#.", "..#..", ".#.#.", "#...#"],
+        'Y' => ["#...#", ".#.#.", "..#..", "..#..", "..#.."],
+        'Z' => ["#####", "...#.", "..#..", ".#...", "#####"],
+        ':' => [".....", "..#..", ".....", "..#..", "....."],
+        '.' => [".....", ".....", ".....", ".....", "..#.."],
+        '-' => [".....", ".....", ".###.", ".....", "....."],
+        '!' => ["..#..", "..#..", "..#..", ".....", "..#.."],
+        // katakana of コロディス
+        'コ' => ["#####", "....#", "....#", "....#", "#####"],
+        'ロ' => ["#####", "#...#", "#...#", "#...#", "#####"],
+        'デ' => ["###.#", "...#.", "#####", "..#..", ".#..."],
+        'ィ' => [".....", "...#.", "..##.", ".#.#.", "...#."],
+        'ス' => ["####.", "...#.", "..#..", ".#.#.", "#...#"],
+        _ => return None,
+    })
+}
+
+/// size in pixels (width, height) of `text` once rendered with the block font
+pub fn text_size(text: &str) -> (usize, usize) {
+    let n = text.chars().count();
+    match n {
+        0 => (0, 0),
+        n => (n * GLYPH_WIDTH + (n - 1) * GLYPH_SPACING, GLYPH_HEIGHT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_fill_their_box() {
+        let text = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ :.-!コロディス";
+        for c in text.chars() {
+            let glyph = glyph(c).unwrap_or_else(|| panic!("no glyph for `{c}`"));
+            for line in glyph {
+                assert_eq!(line.chars().count(), GLYPH_WIDTH, "`{c}`: `{line}`");
+                assert!(
+                    line.chars().all(|p| p == '#' || p == '.'),
+                    "`{c}`: `{line}`"
+                );
+            }
+        }
+        assert_eq!(glyph('a'), glyph('A'));
+        assert!(glyph('?').is_none());
+    }
+
+    #[test]
+    fn text_size_counts_the_spacing() {
+        assert_eq!(text_size(""), (0, 0));
+        assert_eq!(text_size("A"), (GLYPH_WIDTH, GLYPH_HEIGHT));
+        assert_eq!(text_size("GAME"), (4 * GLYPH_WIDTH + 3, GLYPH_HEIGHT));
+    }
+}
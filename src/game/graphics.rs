@@ -1,85 +1,176 @@
 use std::io::{self, Write};
 
+use anyhow::{anyhow, bail, Result};
 use termion::raw::{IntoRawMode, RawTerminal};
 use termsize::Size;
 
-use crate::{
-    cprintln,
-    utils::{Uvec2, SGR},
-};
+use crate::utils::{fmt_duration, fmt_duration_ms, Ivec2, Uvec2, SGR};
 
 use std::io::{BufWriter, StdoutLock};
 
-use super::{GameManager, BACKGROUD_COLOR};
+use super::{
+    board::HIDDEN_ROWS,
+    font::{self, GLYPH_HEIGHT, GLYPH_SPACING, GLYPH_WIDTH},
+    input::{key_name, Action},
+    menu::{MenuEntry, MenuItem},
+    mode::{Ending, GameMode, Ranking},
+    puzzle::Goal,
+    scores::ScoreEntry,
+    stats::{Scoring, MASTER_MAX_LEVEL, SPLIT_LINES},
+    theme::Palette,
+    types::Tetrominoe,
+    GameManager, BACKGROUD_COLOR, CALLOUT_TICKS, TICKS_PER_SECOND,
+};
+
+/// width of the hold (left) and next (right) panels, in terminal cells
+const PANEL_COLS: usize = 12;
+/// columns kept on the right of the box for the stats
+const HUD_COLS: usize = 14;
+/// box height under which the screen titles are written in plain text
+const BIG_WORDS_MIN_ROWS: u16 = 30;
+
+/// how the cleared rows disappear during the line clear delay
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum ClearAnimation {
+    /// the rows blink
+    #[default]
+    Flash,
+    /// the rows empty from the center outwards
+    Wipe,
+    /// the blocks vanish one by one in a random looking order
+    Dissolve,
+}
+
+impl ClearAnimation {
+    pub const ALL: [ClearAnimation; 3] = [
+        ClearAnimation::Flash,
+        ClearAnimation::Wipe,
+        ClearAnimation::Dissolve,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClearAnimation::Flash => "flash",
+            ClearAnimation::Wipe => "wipe",
+            ClearAnimation::Dissolve => "dissolve",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ClearAnimation::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    /// color of the block at (`x`, `y`) of a cleared row `width` blocks wide, `progress` going
+    /// from 0 to 1 over the delay
+    fn color(
+        &self,
+        color: SGR,
+        flash: SGR,
+        (x, y): (usize, usize),
+        width: usize,
+        progress: f32,
+    ) -> SGR {
+        let hidden = match self {
+            ClearAnimation::Flash => {
+                return match ((progress * 8.0) as usize).is_multiple_of(2) {
+                    true => flash,
+                    false => BACKGROUD_COLOR,
+                }
+            }
+            ClearAnimation::Wipe => {
+                let center = (width as f32 - 1.0) / 2.0;
+                (x as f32 - center).abs() < progress * width as f32 / 2.0
+            }
+            ClearAnimation::Dissolve => {
+                let noise = (x.wrapping_mul(2_654_435_761) ^ y.wrapping_mul(40_503)) % 97;
+                (noise as f32) < progress * 97.0
+            }
+        };
+        match hidden {
+            true => BACKGROUD_COLOR,
+            false => color,
+        }
+    }
+}
 
 pub struct GameGraphics {
     screen: BufWriter<RawTerminal<StdoutLock<'static>>>,
     pub term_size: Size,
     pub box_size: Size,
-    pub inner_box_size: Size,
     pub offset: Size,
+    /// a block is drawn `2 * scale` cells wide and `scale` cells high
     pub scale: usize,
+    /// board width in blocks
+    pub board_width: usize,
 }
 
 impl GameGraphics {
-    pub fn init() -> Self {
-        let stdout = match io::stdout().lock().into_raw_mode() {
-            Ok(stdout) => stdout,
-            Err(_) => {
-                cprintln!(
-                    "Couldn't get terminal into raw mode. Please use a modern terminal",
-                    SGR::RedFG
-                );
-                std::process::exit(1);
-            }
-        };
-        let screen = io::BufWriter::new(stdout);
+    /// `width` and `height` are the board size in blocks, the terminal size only decides the scale
+    pub fn init(width: usize, height: usize) -> Result<Self> {
+        let size = termsize::get().ok_or(anyhow!(
+            "Couldn't get terminal dimension. Please use a modern terminal"
+        ))?; // Size { rows: 45, cols: 190 }
 
-        let size = match termsize::get() {
-            Some(s) => s,
-            None => {
-                cprintln!(
-                    "Couldn't get terminal dimension. Please use a modern terminal",
-                    SGR::RedFG
-                );
-                std::process::exit(1);
-            }
-        }; // Size { rows: 45, cols: 190 }
-
-        if size.cols / 2 < 50 || size.rows - 2 < 30 {
-            cprintln!("Terminal window too small to render コロディス", SGR::RedFG);
-            std::process::exit(1);
-        }
-
-        let tetris_size = Size {
-            rows: size.rows - 2,
-            cols: size.cols / 2,
-        };
-        let offset = Size {
-            rows: 1,
-            cols: size.cols / 4,
+        // hold panel | playfield | next panel, each part framed by a column of the box
+        let box_size = |scale: usize| Size {
+            rows: (height * scale + 2) as u16,
+            cols: (2 * PANEL_COLS + 2 * width * scale + 4) as u16,
         };
+        let fits =
+            |b: &Size| b.cols as usize + HUD_COLS <= size.cols as usize && b.rows <= size.rows;
 
-        let border_x = tetris_size.cols - (tetris_size.cols / 5);
-        let inner_box_size = Size {
-            cols: border_x,
-            rows: tetris_size.rows,
+        // the biggest blocks that fit
+        let Some(scale) = (1..).take_while(|s| fits(&box_size(*s))).last() else {
+            let needed = box_size(1);
+            bail!(
+                "Terminal window too small for a {width}x{height} board ({}x{} needed, got {}x{})",
+                needed.cols as usize + HUD_COLS,
+                needed.rows,
+                size.cols,
+                size.rows
+            );
         };
+        let box_size = box_size(scale);
 
-        let scale = if size.cols < 145 || size.rows < 36 {
-            2
-        } else {
-            3
+        // only once the size is known to be fine, so that the errors above are printed normally
+        let stdout = io::stdout().lock().into_raw_mode().map_err(|_| {
+            anyhow!("Couldn't get terminal into raw mode. Please use a modern terminal")
+        })?;
+        let screen = io::BufWriter::new(stdout);
+
+        // centered, stats included
+        let offset = Size {
+            rows: (size.rows - box_size.rows) / 2,
+            cols: (size.cols - box_size.cols - HUD_COLS as u16) / 2,
         };
 
-        Self {
+        Ok(Self {
             screen,
-            box_size: tetris_size,
+            box_size,
             offset,
-            inner_box_size,
             scale,
+            board_width: width,
             term_size: size,
-        }
+        })
+    }
+
+    /// terminal cell of the top left block of the visible field
+    pub fn playfield_origin(&self) -> Uvec2 {
+        Uvec2::new(
+            self.offset.cols as usize + PANEL_COLS + 2,
+            self.offset.rows as usize + 1,
+        )
+    }
+
+    /// top left terminal cell inside the hold panel
+    pub fn hold_panel_origin(&self) -> Uvec2 {
+        Uvec2::new(self.offset.cols as usize + 1, self.offset.rows as usize + 1)
+    }
+
+    /// top left terminal cell inside the next panel
+    pub fn next_panel_origin(&self) -> Uvec2 {
+        let origin = self.playfield_origin();
+        Uvec2::new(origin.x + 2 * self.board_width * self.scale + 1, origin.y)
     }
 
     // writing tool
@@ -97,6 +188,14 @@ impl GameGraphics {
     pub fn show_cursor(&mut self) -> io::Result<()> {
         write!(self.screen, "\x1b[?25h")
     }
+    // terminal modes
+    pub fn enable_focus_reporting(&mut self) -> io::Result<()> {
+        write!(self.screen, "\x1b[?1004h")
+    }
+    pub fn disable_focus_reporting(&mut self) -> io::Result<()> {
+        write!(self.screen, "\x1b[?1004l")
+    }
+
     pub fn clear(&mut self) -> io::Result<()> {
         write!(self.screen, "\x1b[2J")
     }
@@ -130,8 +229,11 @@ impl GameGraphics {
 
 impl GameManager {
     pub fn draw_tetris_box(&mut self) {
+        let Palette {
+            box_color, title, ..
+        } = self.theme().palette();
+
         /* Box Drawing */
-        const BOX_COLOR: SGR = SGR::YellowBG;
         let (h, w) = (
             self.graphics.box_size.rows as usize,
             self.graphics.box_size.cols as usize,
@@ -144,195 +246,881 @@ impl GameManager {
         for y in (oy)..(h + oy) {
             if y == oy || y == h + oy - 1 {
                 for x in ox..(w + ox) {
-                    self.cells[y][x] = BOX_COLOR;
+                    self.cells[y][x] = box_color;
                 }
             } else {
-                self.cells[y][ox] = BOX_COLOR;
-                self.cells[y][w + ox - 1] = BOX_COLOR;
+                self.cells[y][ox] = box_color;
+                self.cells[y][w + ox - 1] = box_color;
             }
         }
 
-        // border delimiter for "next" tedrinos
-        let border_x = ox + w - (w / 5);
-        for y in (oy)..(h + oy) {
-            self.cells[y][border_x] = BOX_COLOR;
-        }
-
         /* Title Drawing */
+        const TITLE: &str = "コロディス";
         let (rt_ox, rt_oy) = ((self.graphics.term_size.cols / 15) as usize, 2_usize);
-        let (rt_w, rt_h) = (
-            (self.graphics.term_size.cols / 12) as usize,
-            ((self.graphics.term_size.rows - 2) / 6) as usize,
+
+        // letters are stacked vertically on the left of the box, take the biggest scale that fits
+        let n = TITLE.chars().count();
+        let (avail_w, avail_h) = (
+            ox.saturating_sub(rt_ox + 1),
+            self.graphics.term_size.rows as usize - rt_oy,
         );
+        let Some(scale) = (1..)
+            .take_while(|s| {
+                GLYPH_WIDTH * 2 * s <= avail_w && n * (GLYPH_HEIGHT + GLYPH_SPACING) * s <= avail_h
+            })
+            .last()
+        else {
+            return; // no room left for the title
+        };
 
-        // first letter - コ
-        const FL_COLOR: SGR = SGR::BlueBG;
-        for x in rt_ox..(rt_w + rt_ox) {
-            self.cells[rt_oy][x] = FL_COLOR;
-            self.cells[rt_oy + rt_h - 1][x] = FL_COLOR;
+        for (i, (letter, color)) in TITLE.chars().zip(title).enumerate() {
+            let y = rt_oy + i * (GLYPH_HEIGHT + GLYPH_SPACING) * scale;
+            self.draw_text_blocks(&letter.to_string(), Uvec2::new(rt_ox, y), scale, &[color]);
         }
-        for y in rt_oy..(rt_h + rt_oy) {
-            self.cells[y][rt_ox + rt_w] = FL_COLOR;
+    }
+
+    /// columns between the playfield and the hold and next panels, only shown in game
+    pub fn draw_panels(&mut self) {
+        let box_color = self.theme().palette().box_color;
+        let oy = self.graphics.offset.rows as usize;
+        let h = self.graphics.box_size.rows as usize;
+        let left = self.graphics.playfield_origin().x - 1;
+        let right = self.graphics.next_panel_origin().x - 1;
+        for y in oy..(oy + h) {
+            self.cells[y][left] = box_color;
+            self.cells[y][right] = box_color;
         }
+    }
 
-        // second letter - ロ
-        const SL_COLOR: SGR = SGR::CyanBG;
-        let base_y = rt_oy + rt_h + 1;
-        for x in rt_ox..(rt_w + rt_ox) {
-            self.cells[base_y][x] = SL_COLOR;
-            self.cells[base_y + rt_h - 2][x] = SL_COLOR;
+    /// blanks every cell of the screen (both color and text layers)
+    pub fn clear_screen(&mut self) {
+        for row in self.cells.iter_mut() {
+            for c in row.iter_mut() {
+                *c = BACKGROUD_COLOR
+            }
         }
-        for y in (rt_oy + rt_h + 1)..(base_y + rt_h) {
-            self.cells[y][rt_ox] = SL_COLOR;
-            self.cells[y][rt_ox + rt_w] = SL_COLOR;
+        self.clear_texts();
+    }
+
+    /// terminal column at the center of the playfield (and of the box)
+    fn playfield_center_x(&self) -> usize {
+        self.graphics.playfield_origin().x + self.graphics.board_width * self.graphics.scale
+    }
+
+    /// draws a vertical list of (label, enabled) entries centered on `cx`, the selected one being highlighted
+    fn draw_menu_list(
+        &mut self,
+        labels: &[(String, bool)],
+        selected: usize,
+        cx: usize,
+        y: usize,
+        spacing: usize,
+    ) {
+        const ITEM_COLOR: SGR = SGR::BrightWhiteFG;
+        const SELECTED_COLOR: SGR = SGR::BrightYellowFG;
+        const DISABLED_COLOR: SGR = SGR::BrightBlackFG;
+
+        for (i, (label, enabled)) in labels.iter().enumerate() {
+            let (label, color) = match (i == selected, enabled) {
+                (true, _) => (format!("> {label} <"), SELECTED_COLOR),
+                (false, true) => (label.clone(), ITEM_COLOR),
+                (false, false) => (label.clone(), DISABLED_COLOR),
+            };
+            self.draw_text_centered(&label, cx, y + i * spacing, color);
+        }
+    }
+
+    /// rows between two items of a list of `n` items starting at the `y` row: 2, or 1 when the
+    /// list wouldn't fit above the hint at the bottom of the box
+    fn list_spacing(&self, n: usize, y: usize) -> usize {
+        let hint_y = self.graphics.offset.rows as usize + self.graphics.box_size.rows as usize - 3;
+        match y + 2 * n < hint_y {
+            true => 2,
+            false => 1,
         }
+    }
 
-        // third letter - デ
-        const TL_COLOR: SGR = SGR::GreenBG;
-        let base_y = rt_oy + rt_h * 2 + 2;
+    pub fn draw_menu(&mut self) {
+        const HINT_COLOR: SGR = SGR::BrightBlackFG;
+        const WARNING_COLOR: SGR = SGR::BrightRedFG;
 
-        self.cells[base_y][rt_ox + rt_w] = TL_COLOR;
-        self.cells[base_y + 1][rt_ox + rt_w + 1] = TL_COLOR;
+        let cx = self.playfield_center_x();
+        let oy = self.graphics.offset.rows as usize;
+        let y = oy + self.graphics.box_size.rows as usize / 3;
 
-        self.cells[base_y][rt_ox + rt_w + 2] = TL_COLOR;
-        self.cells[base_y + 1][rt_ox + rt_w + 1 + 2] = TL_COLOR;
+        let labels = self
+            .menu
+            .list
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let label = match item {
+                    MenuItem::Play if i == self.menu.list.selected => {
+                        format!("PLAY  < {} >", self.menu.mode_label())
+                    }
+                    MenuItem::Play => format!("PLAY  {}", self.menu.mode_label()),
+                    item => item.label().to_string(),
+                };
+                (label, item.enabled())
+            })
+            .collect::<Vec<_>>();
 
-        for x in (rt_ox + 1)..(rt_w + rt_ox - 1) {
-            self.cells[base_y][x] = TL_COLOR;
+        self.clear_texts();
+        self.draw_menu_list(&labels, self.menu.list.selected, cx, y, 2);
+        // what the selected puzzle is, under the PLAY row
+        if self.menu.mode() == GameMode::Puzzle {
+            let (width, height) = self.board_size();
+            let puzzle = self.menu.puzzle();
+            let (text, color) = match (puzzle.width(), puzzle.min_height()) {
+                _ if puzzle.fits(width, height) => (puzzle.name.clone(), HINT_COLOR),
+                (w, _) if w != width => (format!("needs a {w} wide board"), WARNING_COLOR),
+                (_, h) => (format!("needs a {h} high board"), WARNING_COLOR),
+            };
+            self.draw_text_centered(&text, cx, y + 1, color);
         }
-        for x in rt_ox..(rt_w + rt_ox) {
-            self.cells[base_y + 2][x] = TL_COLOR;
+
+        let hint_y = oy + self.graphics.box_size.rows as usize - 3;
+        self.draw_text_centered(
+            "arrows: move  enter: select  q: quit",
+            cx,
+            hint_y,
+            HINT_COLOR,
+        );
+    }
+
+    /// whether block font text `width` cells wide fits in the playfield.
+    /// Small boxes keep their rows for what's under the text
+    fn big_text_fits(&self, width: usize) -> bool {
+        2 * width + 2 < self.graphics.box_size.cols as usize - 2
+            && self.graphics.box_size.rows >= BIG_WORDS_MIN_ROWS
+    }
+
+    /// draws each word on its own line with the block font, centered in the playfield.
+    /// Falls back to plain text (`fallback_fg`) when the words don't fit, returns the first free row below
+    fn draw_big_words(
+        &mut self,
+        words: &[&str],
+        y: usize,
+        colors: &[SGR],
+        fallback_fg: SGR,
+    ) -> usize {
+        let cx = self.playfield_center_x();
+        let widest = words
+            .iter()
+            .map(|w| font::text_size(w).0)
+            .max()
+            .unwrap_or(0);
+
+        if self.big_text_fits(widest) {
+            let mut y = y;
+            for word in words {
+                let (w, h) = font::text_size(word);
+                self.draw_text_blocks(word, Uvec2::new(cx - w, y), 1, colors);
+                y += h + 1;
+            }
+            y
+        } else {
+            self.draw_text_centered(&words.join(" "), cx, y, fallback_fg);
+            y + 2
+        }
+    }
+
+    pub fn draw_pause(&mut self) {
+        const COLORS: [SGR; 2] = [SGR::CyanBG, SGR::BlueBG];
+        const HINT_COLOR: SGR = SGR::BrightBlackFG;
+
+        let cx = self.playfield_center_x();
+        let oy = self.graphics.offset.rows as usize;
+
+        // stats stay visible, time is frozen
+        self.draw_hud();
+        let y = self.draw_big_words(&["PAUSED"], oy + 3, &COLORS, SGR::BrightCyanFG);
+
+        let labels = self
+            .pause_menu
+            .items
+            .iter()
+            .map(|item| (item.label().to_string(), item.enabled()))
+            .collect::<Vec<_>>();
+        self.draw_menu_list(&labels, self.pause_menu.selected, cx, y + 2, 2);
+
+        let hint_y = oy + self.graphics.box_size.rows as usize - 3;
+        let hint = format!(
+            "{}: resume  {}: quit to menu",
+            self.key_names(Action::Pause),
+            self.key_names(Action::Quit)
+        );
+        self.draw_text_centered(&hint, cx, hint_y, HINT_COLOR);
+    }
+
+    /// keys bound to `action`, as shown in hints
+    fn key_names(&self, action: Action) -> String {
+        self.settings
+            .keys
+            .keys(action)
+            .iter()
+            .map(|k| key_name(*k))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    pub fn draw_settings(&mut self) {
+        const TITLE_COLOR: SGR = SGR::BrightYellowFG;
+        const HINT_COLOR: SGR = SGR::BrightBlackFG;
+        const MESSAGE_COLOR: SGR = SGR::BrightCyanFG;
+
+        let cx = self.playfield_center_x();
+        let oy = self.graphics.offset.rows as usize;
+        let y = oy + 3;
+
+        self.clear_texts();
+        self.draw_text_centered("SETTINGS", cx, y, TITLE_COLOR);
+
+        let labels = self
+            .settings_menu
+            .items
+            .iter()
+            .map(|item| {
+                let label = format!("{:<14}{:>14}", item.label(), self.settings.value(*item));
+                (label, item.enabled())
+            })
+            .collect::<Vec<_>>();
+        let spacing = self.list_spacing(labels.len() + 1, y + 3);
+        self.draw_menu_list(&labels, self.settings_menu.selected, cx, y + 3, spacing);
+
+        if let Some(message) = self.settings_message.clone() {
+            let message_y = y + 3 + spacing * labels.len();
+            self.draw_text_centered(&message, cx, message_y, MESSAGE_COLOR);
         }
-        for i in 0..4 {
-            self.cells[base_y + 3 + i][rt_ox + rt_w / 2 - i] = TL_COLOR;
-            self.cells[base_y + 2 + i][rt_ox + rt_w / 2 - i] = TL_COLOR;
+        let hint_y = oy + self.graphics.box_size.rows as usize - 3;
+        self.draw_text_centered(
+            "arrows: change  enter: select  esc: back",
+            cx,
+            hint_y,
+            HINT_COLOR,
+        );
+    }
+
+    pub fn draw_key_bindings(&mut self) {
+        const TITLE_COLOR: SGR = SGR::BrightYellowFG;
+        const HINT_COLOR: SGR = SGR::BrightBlackFG;
+        const MESSAGE_COLOR: SGR = SGR::BrightCyanFG;
+
+        let cx = self.playfield_center_x();
+        let oy = self.graphics.offset.rows as usize;
+        let y = oy + 3;
+
+        self.clear_texts();
+        self.draw_text_centered("KEY BINDINGS", cx, y, TITLE_COLOR);
+
+        let mut labels = Action::ALL
+            .iter()
+            .map(|action| {
+                let keys = match self.binding_capture {
+                    Some(a) if a == *action => "PRESS A KEY".to_string(),
+                    _ => self.key_names(*action),
+                };
+                let name = action.name().replace('_', " ").to_uppercase();
+                (format!("{name:<14}{keys:>14}"), true)
+            })
+            .collect::<Vec<_>>();
+        labels.push(("BACK".to_string(), true));
+        let spacing = self.list_spacing(labels.len() + 1, y + 3);
+        self.draw_menu_list(&labels, self.bindings_selected, cx, y + 3, spacing);
+
+        if let Some(message) = self.settings_message.clone() {
+            let message_y = y + 3 + spacing * labels.len();
+            self.draw_text_centered(&message, cx, message_y, MESSAGE_COLOR);
         }
-        if rt_ox < rt_ox + rt_w / 2 - 4 {
-            for x in rt_ox..=rt_ox + rt_w / 2 - 4 {
-                self.cells[base_y + 3 + 3][x] = TL_COLOR;
+        let hint = match self.binding_capture {
+            Some(_) => "press the new key  esc: cancel",
+            None => "enter: rebind  backspace: reset all  esc: back",
+        };
+        let hint_y = oy + self.graphics.box_size.rows as usize - 3;
+        self.draw_text_centered(hint, cx, hint_y, HINT_COLOR);
+    }
+
+    pub fn draw_game_over(&mut self) {
+        const COLORS: [SGR; 2] = [SGR::RedBG, SGR::MagentaBG];
+        const SCORE_COLOR: SGR = SGR::WhiteBG;
+        const FINISH_COLORS: [SGR; 2] = [SGR::GreenBG, SGR::CyanBG];
+        const LABEL_COLOR: SGR = SGR::BrightBlackFG;
+        const VALUE_COLOR: SGR = SGR::BrightWhiteFG;
+        const BEST_COLOR: SGR = SGR::BrightYellowFG;
+        const MESSAGE_COLOR: SGR = SGR::BrightCyanFG;
+        const REASON_COLOR: SGR = SGR::BrightRedFG;
+        const AHEAD_COLOR: SGR = SGR::BrightGreenFG;
+        const BEHIND_COLOR: SGR = SGR::BrightRedFG;
+
+        let cx = self.playfield_center_x();
+        let oy = self.graphics.offset.rows as usize;
+        let y = oy + 2;
+        let bottom = oy + self.graphics.box_size.rows as usize - 1;
+
+        self.clear_texts();
+        let puzzle = self.replay.puzzle.clone();
+        let mut y = match self.game_over.ending {
+            Ending::TopOut(top_out) => {
+                let words: &[&str] = match puzzle {
+                    Some(_) => &["FAILED"],
+                    None => &["GAME", "OVER"],
+                };
+                let y = self.draw_big_words(words, y, &COLORS, SGR::BrightRedFG);
+                self.draw_text_centered(top_out.label(), cx, y, REASON_COLOR);
+                y
             }
+            Ending::Failed => {
+                let y = self.draw_big_words(&["FAILED"], y, &COLORS, SGR::BrightRedFG);
+                self.draw_text_centered("OUT OF PIECES", cx, y, REASON_COLOR);
+                y
+            }
+            Ending::Finished if puzzle.is_some() => {
+                let y = self.draw_big_words(&["SOLVED"], y, &FINISH_COLORS, SGR::BrightGreenFG);
+                let name = puzzle.as_ref().map_or("", |p| &p.name);
+                self.draw_text_centered(name, cx, y, AHEAD_COLOR);
+                y
+            }
+            Ending::Finished => {
+                let words: &[&str] = match self.game.mode.time_limit() {
+                    Some(_) => &["TIME", "UP"],
+                    None => &["FINISH"],
+                };
+                let y = self.draw_big_words(words, y, &FINISH_COLORS, SGR::BrightGreenFG);
+                self.draw_text_centered(self.game.mode.name(), cx, y, AHEAD_COLOR);
+                y
+            }
+        };
+        y += 1;
+        let ranking = self.game.mode.ranking();
+
+        // the score in big digits when it fits, with the other stats otherwise
+        let score = self.game.stats.score.to_string();
+        let (w, h) = font::text_size(&score);
+        let big_score = ranking == Ranking::Score && self.big_text_fits(w);
+        if big_score {
+            self.draw_text_blocks(&score, Uvec2::new(cx - w, y), 1, &[SCORE_COLOR]);
+            y += h + 1;
+        }
+        if self.game_over.new_best {
+            let text = match ranking {
+                Ranking::Score => "NEW HIGH SCORE!",
+                Ranking::Time | Ranking::Survival => "NEW PERSONAL BEST!",
+            };
+            self.draw_text_centered(text, cx, y, BEST_COLOR);
         }
+        y += 2;
 
-        // fourth letter - ィ
-        const FOL_COLOR: SGR = SGR::MagentaBG;
-        let base_y = base_y + 3 + 3 + 1;
-        {
-            let mut i = 0;
-            while i * 4 <= rt_w {
-                for j in 0..4 {
-                    self.cells[base_y + i][rt_ox + rt_w - i * 4 - j] = FOL_COLOR;
+        let best = self.game_over.previous_best.clone();
+        let mut rows = match (&puzzle, ranking) {
+            (Some(puzzle), _) => vec![
+                ("GOAL", puzzle.goal.label()),
+                (
+                    "PIECES",
+                    format!("{}/{}", self.game.stats.pieces, puzzle.piece_count()),
+                ),
+                ("LINES", self.game.stats.lines.to_string()),
+                ("TIME", fmt_duration_ms(self.game.stats.elapsed())),
+            ],
+            (None, Ranking::Score) => vec![
+                ("LINES", self.game.stats.lines.to_string()),
+                ("LEVEL", self.game.stats.level.to_string()),
+                ("TIME", fmt_duration(self.game.stats.elapsed())),
+                ("PPS", format!("{:.2}", self.game.stats.pps())),
+                (
+                    "T-SPINS",
+                    format!(
+                        "{} ({} mini)",
+                        self.game.stats.t_spins, self.game.stats.mini_t_spins
+                    ),
+                ),
+                (
+                    "BEST",
+                    best.as_ref()
+                        .map_or("-".to_string(), |best| best.score.to_string()),
+                ),
+            ],
+            (None, Ranking::Time) => vec![
+                ("TIME", fmt_duration_ms(self.game.stats.elapsed())),
+                ("PPS", format!("{:.2}", self.game.stats.pps())),
+                ("PIECES", self.game.stats.pieces.to_string()),
+                (
+                    "FINESSE",
+                    match self.game.judges_finesse {
+                        true => format!("{} faults", self.game.stats.finesse_faults),
+                        false => "-".to_string(),
+                    },
+                ),
+                (
+                    "BEST",
+                    best.as_ref()
+                        .map_or("-".to_string(), |best| fmt_duration_ms(best.time)),
+                ),
+            ],
+            (None, Ranking::Survival) => vec![
+                ("TIME", fmt_duration(self.game.stats.elapsed())),
+                ("LINES", self.game.stats.lines.to_string()),
+                ("GARBAGE", self.game.garbage_risen.to_string()),
+                ("PPS", format!("{:.2}", self.game.stats.pps())),
+                ("SCORE", self.game.stats.score.to_string()),
+                (
+                    "BEST",
+                    best.as_ref().map_or("-".to_string(), |best| {
+                        format!("{} {}L", fmt_duration(best.time), best.lines)
+                    }),
+                ),
+            ],
+        };
+        if ranking == Ranking::Score && !big_score {
+            rows.insert(0, ("SCORE", score));
+        }
+        if let Some(grade) = self.game.mode.grade(&self.game.stats) {
+            rows.insert(0, ("GRADE", grade.to_string()));
+        }
+        for (label, value) in rows {
+            self.draw_text(label, cx - 10, y, LABEL_COLOR);
+            self.draw_text(&value, cx + 10 - value.len(), y, VALUE_COLOR);
+            y += 1;
+        }
+        y += 1;
+
+        let labels = self
+            .game_over
+            .list
+            .items
+            .iter()
+            .map(|item| (item.label().to_string(), item.enabled()))
+            .collect::<Vec<_>>();
+
+        // split times against the best ones, as many as there's room for above the menu
+        if ranking == Ranking::Time && puzzle.is_none() {
+            let room = bottom.saturating_sub(y + labels.len() + 3);
+            let splits = self.game.stats.splits.clone();
+            for (i, split) in splits.iter().enumerate().take(room) {
+                let label = format!("{}L", (i + 1) * SPLIT_LINES);
+                let time = fmt_duration_ms(*split);
+                self.draw_text(&label, cx - 10, y, LABEL_COLOR);
+                self.draw_text(&time, cx + 3 - time.len(), y, VALUE_COLOR);
+                if let Some(best) = best.as_ref().and_then(|b| b.splits.get(i)) {
+                    let (delta, color) = match split >= best {
+                        true => (
+                            format!("+{:.3}", (*split - *best).as_secs_f64()),
+                            BEHIND_COLOR,
+                        ),
+                        false => (
+                            format!("-{:.3}", (*best - *split).as_secs_f64()),
+                            AHEAD_COLOR,
+                        ),
+                    };
+                    self.draw_text(&delta, cx + 10 - delta.len(), y, color);
                 }
-                i += 1;
+                y += 1;
+            }
+            if !splits.is_empty() && room > 0 {
+                y += 1;
             }
         }
-        for y in (base_y + 1)..(base_y + 1 + rt_h) {
-            self.cells[y][rt_ox + rt_w / 2] = FOL_COLOR;
-            self.cells[y][rt_ox + rt_w / 2 + 1] = FOL_COLOR;
+
+        self.draw_menu_list(&labels, self.game_over.list.selected, cx, y, 1);
+        y += labels.len() + 1;
+
+        for message in self.game_over.messages.clone() {
+            self.draw_text_centered(&message, cx, y, MESSAGE_COLOR);
+            y += 1;
         }
+    }
+
+    pub fn draw_replays(&mut self) {
+        const TITLE_COLOR: SGR = SGR::BrightYellowFG;
+        const HINT_COLOR: SGR = SGR::BrightBlackFG;
+        const MESSAGE_COLOR: SGR = SGR::BrightCyanFG;
+
+        let cx = self.playfield_center_x();
+        let oy = self.graphics.offset.rows as usize;
+        let y = oy + 3;
+        let hint_y = oy + self.graphics.box_size.rows as usize - 3;
 
-        // fifth letter - ス
-        const FIL_COLOR: SGR = SGR::RedBG;
-        let base_y = base_y + 1 + rt_h + 1;
-        for x in rt_ox..(rt_w + rt_ox) {
-            self.cells[base_y][x] = FIL_COLOR;
+        self.clear_texts();
+        self.draw_text_centered("REPLAYS", cx, y, TITLE_COLOR);
+
+        // only the page of files holding the selected one fits between the title and the hint
+        let page = hint_y.saturating_sub(y + 5).max(1);
+        let first = self.replays_selected / page * page;
+        let labels = self
+            .replay_files
+            .iter()
+            .skip(first)
+            .take(page)
+            .map(|path| {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                (name.to_string(), true)
+            })
+            .collect::<Vec<_>>();
+        self.draw_menu_list(&labels, self.replays_selected - first, cx, y + 2, 1);
+
+        if let Some(message) = self.replays_message.clone() {
+            self.draw_text_centered(&message, cx, y + 3 + labels.len(), MESSAGE_COLOR);
         }
-        {
-            let mut i = 0;
-            'outer: while i * 2 <= rt_w {
-                for j in 0..2 {
-                    if rt_w - i * 2 - j - 1 == 0 {
-                        break 'outer;
-                    }
-                    self.cells[base_y + 1 + i][rt_ox + rt_w - i * 2 - j - 1] = FIL_COLOR;
-                }
-                i += 1;
+        self.draw_text_centered("enter: watch  esc: back", cx, hint_y, HINT_COLOR);
+    }
+
+    pub fn draw_high_scores(&mut self) {
+        const TITLE_COLOR: SGR = SGR::BrightYellowFG;
+        const HEADER_COLOR: SGR = SGR::BrightBlackFG;
+        const ROW_COLOR: SGR = SGR::BrightWhiteFG;
+
+        let cx = self.playfield_center_x();
+        let mut y = self.graphics.offset.rows as usize + 3;
+
+        self.clear_texts();
+        self.draw_text_centered("HIGH SCORES", cx, y, TITLE_COLOR);
+        y += 2;
+        let mode = self.menu.mode();
+        self.draw_text_centered(&format!("< {} >", mode.name()), cx, y, ROW_COLOR);
+        y += 1;
+        // scores are kept per rules, the ones a game of that mode would be played with
+        let rules = mode.rules().unwrap_or(self.settings.rules);
+        self.draw_text_centered(&rules.name().to_uppercase(), cx, y, HEADER_COLOR);
+        y += 2;
+
+        let x = cx.saturating_sub(18);
+        self.draw_text(&ScoreEntry::table_header(), x, y, HEADER_COLOR);
+        y += 1;
+
+        let rows = self
+            .high_scores
+            .top(mode, rules)
+            .iter()
+            .enumerate()
+            .map(|(i, e)| e.table_row(i + 1))
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            self.draw_text_centered("no score yet", cx, y + 1, HEADER_COLOR);
+        }
+        for row in rows {
+            self.draw_text(&row, x, y, ROW_COLOR);
+            y += 1;
+        }
+    }
+
+    // primitives
+    /// paints the `w` x `h` cells whose top left one is (x, y)
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, c: SGR) {
+        for row in self.cells.iter_mut().skip(y).take(h) {
+            for cell in row.iter_mut().skip(x).take(w) {
+                *cell = c
             }
         }
-        {
-            let mut i = 0;
-            'outer: while i * 2 <= rt_w {
-                for j in 0..2 {
-                    if rt_w + i * 2 + j + 1 == rt_w + rt_ox - 3 {
-                        break 'outer;
+    }
+
+    /// places `text` into the text layer, starting at the (x, y) terminal cell
+    pub fn draw_text(&mut self, text: &str, x: usize, y: usize, fg: SGR) {
+        let Some(row) = self.texts.get_mut(y) else {
+            return;
+        };
+        for (i, ch) in text.chars().enumerate() {
+            match row.get_mut(x + i) {
+                Some(cell) => *cell = Some((ch, fg)),
+                None => break,
+            }
+        }
+    }
+
+    /// same as `draw_text` but `text` is centered on the `cx` column
+    pub fn draw_text_centered(&mut self, text: &str, cx: usize, y: usize, fg: SGR) {
+        let x = cx.saturating_sub(text.chars().count() / 2);
+        self.draw_text(text, x, y, fg)
+    }
+
+    pub fn clear_texts(&mut self) {
+        for row in self.texts.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell = None
+            }
+        }
+    }
+
+    /// draws `text` with the block font, `origin` being the top left terminal cell.
+    /// A font pixel is `2*scale` cells wide and `scale` cells high, glyphs cycle through `colors`,
+    /// nothing is drawn without colors
+    pub fn draw_text_blocks(&mut self, text: &str, origin: Uvec2, scale: usize, colors: &[SGR]) {
+        if colors.is_empty() {
+            return;
+        }
+        let (px_w, px_h) = (2 * scale, scale);
+        for (i, c) in text.chars().enumerate() {
+            let Some(glyph) = font::glyph(c) else {
+                continue;
+            };
+            let color = colors[i % colors.len()];
+            let gx = origin.x + i * (GLYPH_WIDTH + GLYPH_SPACING) * px_w;
+
+            for (py, line) in glyph.iter().enumerate() {
+                for (px, pixel) in line.chars().enumerate() {
+                    if pixel != '#' {
+                        continue;
+                    }
+                    for y in (origin.y + py * px_h)..(origin.y + (py + 1) * px_h) {
+                        for x in (gx + px * px_w)..(gx + (px + 1) * px_w) {
+                            if let Some(cell) = self.cells.get_mut(y).and_then(|r| r.get_mut(x)) {
+                                *cell = color
+                            }
+                        }
                     }
-                    self.cells[base_y + 1 + i + rt_h / 2][rt_ox + rt_w / 2 + i * 2 + j + 1] =
-                        FIL_COLOR;
                 }
-                i += 1;
             }
         }
     }
 
-    // primitives
-    pub fn draw_square(&mut self, scale: usize, x: usize, y: usize, c: SGR) {
-        if scale == 1 {
-            self.cells[y][x] = c;
-        } else {
-            let gapscale = scale - 1;
-            for i in 0..gapscale {
-                for j in 0..gapscale {
-                    self.cells[y + i][x + j] = c;
+    /// paints the visible rows of the board with the ghost and the falling piece, then the previews
+    pub fn draw_playfield(&mut self) {
+        let palette = self.theme().palette();
+        let mut field = self
+            .game
+            .board
+            .visible_rows()
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|c| c.map_or(BACKGROUD_COLOR, |b| palette.block(b)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(clear) = &self.game.line_clear {
+            let progress =
+                (clear.ticks + 1) as f32 / self.game.rules.line_clear_delay.max(1) as f32;
+            let animation = self.settings.clear_animation;
+            for &y in &clear.rows {
+                let Some(row) = y.checked_sub(HIDDEN_ROWS).and_then(|y| field.get_mut(y)) else {
+                    continue;
+                };
+                let width = row.len();
+                for (x, cell) in row.iter_mut().enumerate() {
+                    *cell = animation.color(*cell, palette.flash, (x, y), width, progress);
                 }
             }
         }
-    }
 
-    // higher level abstraction over squares
-    pub fn draw_tetrominoe(&mut self, c: SGR) {
-        let (oy, ox) = (
-            self.graphics.offset.rows as usize,
-            self.graphics.offset.cols as usize,
-        );
-        for Uvec2 { x, y } in self.tetrominoe.vertices_pos.clone() {
-            self.draw_square(self.tetrominoe.scale, x + ox, y + oy, c);
+        let piece = &self.game.tetrominoe;
+        let mut layers = vec![];
+        // between two pieces the last one is already part of the board
+        if self.game.piece_in_play() {
+            if self.settings.ghost && self.game.rules.ghost {
+                let fall = self.game.board.drop_distance(&piece.cells());
+                layers.push((piece.moved(0, fall as isize).cells(), palette.ghost));
+            }
+            layers.push((piece.cells(), palette.piece(piece.ttype)));
+        }
+        for (blocks, color) in layers {
+            // the hidden rows aren't drawn
+            for Ivec2 { x, y } in blocks {
+                if let Some(cell) = field
+                    .get_mut((y - HIDDEN_ROWS as isize) as usize)
+                    .and_then(|row| row.get_mut(x as usize))
+                {
+                    *cell = color
+                }
+            }
         }
-    }
 
-    pub fn clear_tetrominoe(&mut self) {
-        self.draw_tetrominoe(BACKGROUD_COLOR)
+        let origin = self.graphics.playfield_origin();
+        let scale = self.graphics.scale;
+        for (by, row) in field.into_iter().enumerate() {
+            for (bx, color) in row.into_iter().enumerate() {
+                let (x, y) = (origin.x + bx * 2 * scale, origin.y + by * scale);
+                self.fill_rect(x, y, 2 * scale, scale, color);
+            }
+        }
+        self.draw_previews();
     }
 
-    pub fn draw_nt(&mut self) {
-        let (oy, ox) = (
-            self.graphics.offset.rows as usize,
-            self.graphics.offset.cols as usize,
-        );
-        for i in 0..3 {
-            for Uvec2 { x, y } in self.next_tetrominoes[i].vertices_pos.clone() {
-                self.draw_square(
-                    self.next_tetrominoes[i].scale,
-                    x + ox,
-                    y + oy,
-                    self.next_tetrominoes[i].color,
-                );
+    /// held and next pieces in the side panels, the ones that don't fit are left out
+    fn draw_previews(&mut self) {
+        const LABEL_COLOR: SGR = SGR::BrightBlackFG;
+        const CALLOUT_COLOR: SGR = SGR::BrightMagentaFG;
+
+        let rows = self.graphics.box_size.rows as usize - 2;
+        let hold = self.graphics.hold_panel_origin();
+        let next = self.graphics.next_panel_origin();
+        let bottom = hold.y + rows;
+        self.fill_rect(hold.x, hold.y, PANEL_COLS, rows, BACKGROUD_COLOR);
+        self.fill_rect(next.x, next.y, PANEL_COLS, rows, BACKGROUD_COLOR);
+
+        self.draw_text_centered("HOLD", hold.x + PANEL_COLS / 2, hold.y, LABEL_COLOR);
+        if let Some(held) = self.game.held.clone() {
+            self.draw_preview(&held, hold.x, hold.y + 2, bottom);
+        }
+        // last T-spin under the held piece, one word per line
+        if let Some((words, tick)) = self.game.callout.clone() {
+            if self.game.tick - tick < CALLOUT_TICKS {
+                let cx = hold.x + PANEL_COLS / 2;
+                for (i, word) in words
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| hold.y + 5 + i < bottom)
+                {
+                    self.draw_text_centered(word, cx, hold.y + 5 + i, CALLOUT_COLOR);
+                }
+            }
+        }
+
+        self.draw_text_centered("NEXT", next.x + PANEL_COLS / 2, next.y, LABEL_COLOR);
+        let mut y = next.y + 2;
+        let pieces = self.game.next_tetrominoes.clone();
+        for piece in pieces
+            .iter()
+            .take(self.settings.next_count.min(self.game.rules.previews))
+        {
+            match self.draw_preview(piece, next.x, y, bottom) {
+                Some(h) => y += h + 1,
+                None => break,
             }
         }
     }
 
-    pub fn clear_nt(&mut self) {
+    /// draws `piece` with one cell high blocks, centered in the panel starting at the `panel_x`
+    /// column with its top on the `y` row. Returns its height, `None` if it goes below `bottom`
+    fn draw_preview(
+        &mut self,
+        piece: &Tetrominoe,
+        panel_x: usize,
+        y: usize,
+        bottom: usize,
+    ) -> Option<usize> {
+        let (min_x, max_x, min_y, max_y) = piece.bounds();
+        let (w, h) = ((max_x - min_x + 1) as usize, (max_y - min_y + 1) as usize);
+        if y + h > bottom {
+            return None;
+        }
+        let x = panel_x + (PANEL_COLS - 2 * w) / 2;
+        let color = self.theme().palette().piece(piece.ttype);
+        for b in piece.blocks {
+            let (bx, by) = ((b.x - min_x) as usize, (b.y - min_y) as usize);
+            self.fill_rect(x + 2 * bx, y + by, 2, 1, color);
+        }
+        Some(h)
+    }
+
+    /// side panel with the game stats, drawn on the right of the box
+    pub fn draw_hud(&mut self) {
+        const LABEL_COLOR: SGR = SGR::BrightBlackFG;
+        const VALUE_COLOR: SGR = SGR::BrightWhiteFG;
+
         let (oy, ox) = (
             self.graphics.offset.rows as usize,
             self.graphics.offset.cols as usize,
         );
-        for i in 0..3 {
-            for Uvec2 { x, y } in self.next_tetrominoes[i].vertices_pos.clone() {
-                self.draw_square(
-                    self.next_tetrominoes[i].scale,
-                    x + ox,
-                    y + oy,
-                    BACKGROUD_COLOR,
-                );
+        let x = ox + self.graphics.box_size.cols as usize + 3;
+
+        let combo = match self.game.stats.combo {
+            Some(c) if c > 0 => format!("x{c}"),
+            _ => "-".to_string(),
+        };
+        let b2b = if self.game.stats.b2b { "ready" } else { "-" };
+        let height = self.game.board.visible_rows().len();
+        let line_goal = match self.game.goal {
+            Some(Goal::Lines(lines)) => Some(lines),
+            _ => self.game.mode.line_goal(),
+        };
+        let lines = match (line_goal, self.game.mode.garbage_rows(height)) {
+            (Some(goal), _) => ("LINES", format!("{}/{goal}", self.game.stats.lines)),
+            // garbage rows left to dig through
+            (None, garbage) if garbage > 0 => {
+                ("GARBAGE", format!("{}/{garbage}", self.game.board.garbage))
             }
+            (None, _) => ("LINES", self.game.stats.lines.to_string()),
+        };
+        let elapsed = self.game.stats.elapsed();
+        let (time_label, time) = match (
+            self.game.mode.time_left(&self.game.stats),
+            self.game.mode.ranking(),
+        ) {
+            (Some(left), _) => ("TIME LEFT", fmt_duration(left)),
+            (None, Ranking::Score | Ranking::Survival) => ("TIME", fmt_duration(elapsed)),
+            (None, Ranking::Time) => ("TIME", fmt_duration_ms(elapsed)),
+        };
+        let level = match (self.game.goal, self.game.next_rise) {
+            (Some(goal), _) => ("GOAL", goal.label()),
+            // countdown to the next garbage row instead
+            (None, Some(tick)) => (
+                "NEXT ROW",
+                format!(
+                    "{:.1}s",
+                    tick.saturating_sub(self.game.tick) as f64 / TICKS_PER_SECOND as f64
+                ),
+            ),
+            // with the level where the section ends
+            (None, None) if self.game.stats.scoring == Scoring::Master => {
+                let stop = ((self.game.stats.level / 100 + 1) * 100).min(MASTER_MAX_LEVEL);
+                ("LEVEL", format!("{}/{stop}", self.game.stats.level))
+            }
+            (None, None) => ("LEVEL", self.game.stats.level.to_string()),
+        };
+        let last = match self.game.mode.grade(&self.game.stats) {
+            Some(grade) => ("GRADE", grade.to_string()),
+            // placements that can be undone and redone
+            None if self.game.mode.has_undo() => (
+                "UNDO/REDO",
+                format!("{}/{}", self.undo.len(), self.redo.len()),
+            ),
+            None if self.game.goal.is_some() => {
+                ("PIECES LEFT", self.game.pieces_left().to_string())
+            }
+            None => ("B2B", b2b.to_string()),
+        };
+        let rows = [
+            ("SCORE", self.game.stats.score.to_string()),
+            level,
+            lines,
+            (time_label, time),
+            ("PPS", format!("{:.2}", self.game.stats.pps())),
+            ("COMBO", combo),
+            last,
+        ];
+
+        self.clear_texts();
+        for (i, (label, value)) in rows.into_iter().enumerate() {
+            let y = oy + 1 + i * 3;
+            self.draw_text(label, x, y, LABEL_COLOR);
+            self.draw_text(&value, x, y + 1, VALUE_COLOR);
         }
     }
 
-    // paints the screen
+    // paints the cells that changed since the last render
     pub fn render(&mut self) -> io::Result<()> {
+        let ghost = self.theme().palette().ghost;
         for (y, row) in self.cells.iter().enumerate() {
             for (x, c) in row.iter().enumerate() {
+                let cell = Some((*c, self.texts[y][x]));
+                if self.rendered[y][x] == cell {
+                    continue;
+                }
+                self.rendered[y][x] = cell;
+
                 self.graphics.move_cursor(x + 1, y + 1)?;
-                self.graphics.set_colors(&[*c])?;
-                self.graphics.blank()?;
+                if self.launch.no_color {
+                    // blocks in reverse video, the ghost dotted
+                    let (colors, ch) = match self.texts[y][x] {
+                        Some((ch, _)) => (vec![SGR::Reset], ch),
+                        None if *c == BACKGROUD_COLOR => (vec![SGR::Reset], ' '),
+                        None if *c == ghost => (vec![SGR::Reset], '.'),
+                        None => (vec![SGR::Reset, SGR::Reverse], ' '),
+                    };
+                    self.graphics.set_colors(&colors)?;
+                    self.graphics.text(&ch.to_string())?;
+                    continue;
+                }
+                match self.texts[y][x] {
+                    Some((ch, fg)) => {
+                        self.graphics.set_colors(&[*c, fg])?;
+                        self.graphics.text(&ch.to_string())?;
+                    }
+                    None => {
+                        self.graphics.set_colors(&[*c])?;
+                        self.graphics.blank()?;
+                    }
+                }
             }
         }
         Ok(())
@@ -0,0 +1,507 @@
+use std::collections::BTreeMap;
+
+use termion::{
+    event::{Event, Key},
+    input::{Events, TermRead},
+    AsyncReader,
+};
+
+/// focus-in sequence sent by the terminal when focus reporting is enabled (CSI I)
+const FOCUS_IN: &[u8] = b"\x1b[I";
+/// focus-out sequence sent by the terminal when focus reporting is enabled (CSI O)
+const FOCUS_OUT: &[u8] = b"\x1b[O";
+
+pub enum Input {
+    Key(Key),
+    FocusIn,
+    FocusOut,
+}
+
+impl Input {
+    fn from_event(event: Event) -> Option<Self> {
+        match event {
+            Event::Key(key) => Some(Input::Key(key)),
+            // termion doesn't know about focus events, they come back as unsupported sequences
+            Event::Unsupported(seq) if seq == FOCUS_IN => Some(Input::FocusIn),
+            Event::Unsupported(seq) if seq == FOCUS_OUT => Some(Input::FocusOut),
+            _ => None,
+        }
+    }
+}
+
+pub struct InputListener {
+    events: Events<AsyncReader>,
+}
+
+impl InputListener {
+    pub fn init() -> Self {
+        Self {
+            events: termion::async_stdin().events(),
+        }
+    }
+
+    /// returns the next pending input (if any) without blocking
+    pub fn poll(&mut self) -> Option<Input> {
+        loop {
+            match self.events.next()? {
+                Ok(event) => match Input::from_event(event) {
+                    Some(input) => return Some(input),
+                    None => continue,
+                },
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// what the player wants to do, independently of the key that triggered it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+    Undo,
+    Redo,
+    Pause,
+    Restart,
+    Retry,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::HardDrop,
+        Action::RotateCw,
+        Action::RotateCcw,
+        Action::Rotate180,
+        Action::Hold,
+        Action::Undo,
+        Action::Redo,
+        Action::Pause,
+        Action::Restart,
+        Action::Retry,
+        Action::Quit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::HardDrop => "hard_drop",
+            Action::RotateCw => "rotate_cw",
+            Action::RotateCcw => "rotate_ccw",
+            Action::Rotate180 => "rotate_180",
+            Action::Hold => "hold",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Pause => "pause",
+            Action::Restart => "restart",
+            Action::Retry => "retry",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Action::ALL.into_iter().find(|a| a.name() == name)
+    }
+
+    /// keys bound to the action when the config file doesn't say otherwise
+    pub fn default_keys(&self) -> Vec<Key> {
+        match self {
+            Action::MoveLeft => vec![Key::Left, Key::Char('a')],
+            Action::MoveRight => vec![Key::Right, Key::Char('d')],
+            Action::SoftDrop => vec![Key::Down, Key::Char('s')],
+            Action::HardDrop => vec![Key::Char(' ')],
+            Action::RotateCw => vec![Key::Up, Key::Char('e')],
+            Action::RotateCcw => vec![Key::Char('w')],
+            Action::Rotate180 => vec![Key::Char('f')],
+            Action::Hold => vec![Key::Char('c')],
+            Action::Undo => vec![Key::Char('z')],
+            Action::Redo => vec![Key::Char('y')],
+            Action::Pause => vec![Key::Char('p')],
+            Action::Restart => vec![Key::Char('r')],
+            Action::Retry => vec![Key::Char('t')],
+            Action::Quit => vec![Key::Esc, Key::Char('q')],
+        }
+    }
+
+    /// whether the action acts on the game (and thus must be recorded in replays)
+    pub fn is_gameplay(&self) -> bool {
+        !matches!(
+            self,
+            Action::Pause | Action::Restart | Action::Retry | Action::Quit
+        )
+    }
+}
+
+/// parses a key name as written in the config file (`a`, `space`, `left`, `ctrl-c`, `f5`...)
+pub fn parse_key(name: &str) -> Option<Key> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(Key::Char(c));
+    }
+
+    let lower = name.to_lowercase();
+    Some(match lower.as_str() {
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "space" => Key::Char(' '),
+        "enter" => Key::Char('\n'),
+        "tab" => Key::Char('\t'),
+        "esc" | "escape" => Key::Esc,
+        "backspace" => Key::Backspace,
+        "delete" => Key::Delete,
+        "insert" => Key::Insert,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        _ => {
+            let single = |s: &str| {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(c),
+                    _ => None,
+                }
+            };
+            if let Some(c) = lower.strip_prefix("ctrl-").and_then(single) {
+                Key::Ctrl(c)
+            } else if let Some(c) = lower.strip_prefix("alt-").and_then(single) {
+                Key::Alt(c)
+            } else if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                match n {
+                    1..=12 => Key::F(n),
+                    _ => return None,
+                }
+            } else {
+                return None;
+            }
+        }
+    })
+}
+
+/// inverse of `parse_key`
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Left => "left".to_string(),
+        Key::Right => "right".to_string(),
+        Key::Up => "up".to_string(),
+        Key::Down => "down".to_string(),
+        Key::Char(' ') => "space".to_string(),
+        Key::Char('\n') => "enter".to_string(),
+        Key::Char('\t') => "tab".to_string(),
+        Key::Esc => "esc".to_string(),
+        Key::Backspace => "backspace".to_string(),
+        Key::Delete => "delete".to_string(),
+        Key::Insert => "insert".to_string(),
+        Key::Home => "home".to_string(),
+        Key::End => "end".to_string(),
+        Key::PageUp => "pageup".to_string(),
+        Key::PageDown => "pagedown".to_string(),
+        Key::Ctrl(c) => format!("ctrl-{c}"),
+        Key::Alt(c) => format!("alt-{c}"),
+        Key::F(n) => format!("f{n}"),
+        Key::Char(c) => c.to_string(),
+        _ => "?".to_string(),
+    }
+}
+
+/// action of every key, built from the config file
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<Key>)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            bindings: Action::ALL.map(|a| (a, a.default_keys())).to_vec(),
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn action(&self, key: Key) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.bindings
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map_or(&[], |(_, keys)| keys)
+    }
+
+    /// builds the bindings from the `[keys]` table of the config file (action name -> key names).
+    /// Actions missing from the table keep their default keys, minus the ones the table gives to other actions.
+    /// Returns every problem found instead of stopping at the first one
+    pub fn from_names(table: &BTreeMap<String, Vec<String>>) -> Result<Self, Vec<String>> {
+        let mut errors = vec![];
+
+        let mut explicit: Vec<(Action, Vec<Key>)> = vec![];
+        for (name, key_names) in table {
+            let Some(action) = Action::from_name(name) else {
+                errors.push(format!("unknown action `{name}`"));
+                continue;
+            };
+            if key_names.is_empty() {
+                errors.push(format!("`{name}` has no key bound"));
+            }
+
+            let mut keys = vec![];
+            for key_name in key_names {
+                match parse_key(key_name) {
+                    Some(key) if !keys.contains(&key) => keys.push(key),
+                    Some(_) => {}
+                    None => errors.push(format!("invalid key `{key_name}` for `{name}`")),
+                }
+            }
+            explicit.push((action, keys));
+        }
+
+        for (i, (a, keys)) in explicit.iter().enumerate() {
+            for (b, other_keys) in &explicit[i + 1..] {
+                for key in keys.iter().filter(|k| other_keys.contains(k)) {
+                    errors.push(format!(
+                        "key `{}` is bound to both `{}` and `{}`",
+                        key_name(*key),
+                        a.name(),
+                        b.name()
+                    ));
+                }
+            }
+        }
+
+        let bindings = Action::ALL
+            .map(|action| match explicit.iter().find(|(a, _)| *a == action) {
+                Some((_, keys)) => (action, keys.clone()),
+                None => {
+                    let keys = action
+                        .default_keys()
+                        .into_iter()
+                        .filter(|k| !explicit.iter().any(|(_, keys)| keys.contains(k)))
+                        .collect::<Vec<_>>();
+                    if keys.is_empty() {
+                        errors.push(format!(
+                            "`{}` lost all its default keys to other actions, bind it explicitly",
+                            action.name()
+                        ));
+                    }
+                    (action, keys)
+                }
+            })
+            .to_vec();
+
+        match errors.is_empty() {
+            true => Ok(Self { bindings }),
+            false => Err(errors),
+        }
+    }
+    /// action name -> key names, as written in the config file
+    pub fn to_names(&self) -> BTreeMap<String, Vec<String>> {
+        self.bindings
+            .iter()
+            .map(|(action, keys)| {
+                (
+                    action.name().to_string(),
+                    keys.iter().map(|k| key_name(*k)).collect(),
+                )
+            })
+            .collect()
+    }
+
+    /// makes `key` the only key of `action`, taking it from the action it was bound to
+    pub fn rebind(&mut self, action: Action, key: Key) -> Result<(), String> {
+        if let Some(owner) = self.action(key).filter(|owner| *owner != action) {
+            if self.keys(owner).len() == 1 {
+                return Err(format!(
+                    "`{}` is the only key of `{}`",
+                    key_name(key),
+                    owner.name()
+                ));
+            }
+        }
+
+        for (a, keys) in self.bindings.iter_mut() {
+            if *a == action {
+                *keys = vec![key];
+            } else {
+                keys.retain(|k| *k != key);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ticks without auto-repeat event after which a key is considered released
+const RELEASE_TICKS: u64 = 6;
+/// maximum delay between a key press and its first auto-repeat (terminal repeat delays are usually 250-600ms)
+const REPEAT_DELAY_TICKS: u64 = 40;
+
+/// Terminals don't report key releases, only presses and their auto-repeat: a move key is
+/// considered held while its repeat events keep coming, and slides the piece once held for `das` ticks
+#[derive(Default)]
+pub struct AutoShift {
+    action: Option<Action>,
+    pressed_at: u64,
+    last_seen: u64,
+    /// whether an auto-repeat event has been received since the press
+    repeating: bool,
+    last_shift: u64,
+}
+
+impl AutoShift {
+    /// registers a move key event, returns whether it's a new press (that should move the piece once)
+    pub fn press(&mut self, action: Action, tick: u64) -> bool {
+        let is_repeat = self.action == Some(action)
+            && match self.repeating {
+                true => tick - self.last_seen <= RELEASE_TICKS,
+                false => tick - self.pressed_at <= REPEAT_DELAY_TICKS,
+            };
+
+        self.last_seen = tick;
+        if is_repeat {
+            self.repeating = true;
+        } else {
+            self.action = Some(action);
+            self.pressed_at = tick;
+            self.repeating = false;
+        }
+        !is_repeat
+    }
+
+    /// number of shifts to do this tick (`usize::MAX` meaning up to the wall)
+    pub fn update(&mut self, tick: u64, das: usize, arr: usize) -> (Option<Action>, usize) {
+        if !self.repeating {
+            return (None, 0);
+        }
+        if tick - self.last_seen > RELEASE_TICKS {
+            self.action = None;
+            self.repeating = false;
+            return (None, 0);
+        }
+        if tick - self.pressed_at < das as u64 {
+            return (None, 0);
+        }
+
+        match arr {
+            0 => (self.action, usize::MAX),
+            arr if tick - self.last_shift >= arr as u64 => {
+                self.last_shift = tick;
+                (self.action, 1)
+            }
+            _ => (None, 0),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the input read from the bytes sent by the terminal
+    fn read(bytes: &[u8]) -> Option<Input> {
+        Input::from_event(bytes.events().next()?.ok()?)
+    }
+
+    #[test]
+    fn focus_sequences_are_read() {
+        assert!(matches!(read(FOCUS_IN), Some(Input::FocusIn)));
+        assert!(matches!(read(FOCUS_OUT), Some(Input::FocusOut)));
+        assert!(matches!(read(b"p"), Some(Input::Key(Key::Char('p')))));
+        assert!(matches!(read(b"\x1b[D"), Some(Input::Key(Key::Left))));
+        // mouse events aren't used
+        assert!(read(b"\x1b[M\x20\x21\x21").is_none());
+    }
+
+    fn table(entries: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        entries
+            .iter()
+            .map(|(action, keys)| {
+                (
+                    action.to_string(),
+                    keys.iter().map(|k| k.to_string()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn missing_actions_keep_their_free_default_keys() {
+        let bindings = KeyBindings::from_names(&table(&[("hold", &["a", "h"])])).unwrap();
+        assert_eq!(
+            bindings.keys(Action::Hold),
+            [Key::Char('a'), Key::Char('h')]
+        );
+        // `a` went to hold, move_left keeps the arrow only
+        assert_eq!(bindings.keys(Action::MoveLeft), [Key::Left]);
+        assert_eq!(bindings.action(Key::Char('c')), None);
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        let errors = KeyBindings::from_names(&table(&[
+            ("jump", &["j"]),
+            ("hold", &[]),
+            ("pause", &["nope"]),
+            ("move_left", &["x"]),
+            ("move_right", &["x"]),
+        ]))
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "`hold` has no key bound",
+                "unknown action `jump`",
+                "invalid key `nope` for `pause`",
+                "key `x` is bound to both `move_left` and `move_right`",
+            ]
+        );
+    }
+
+    #[test]
+    fn taking_every_default_key_of_an_action_is_an_error() {
+        let errors = KeyBindings::from_names(&table(&[("move_left", &["w"])])).unwrap_err();
+        assert_eq!(
+            errors,
+            ["`rotate_ccw` lost all its default keys to other actions, bind it explicitly"]
+        );
+    }
+    #[test]
+    fn names_round_trip() {
+        let bindings = KeyBindings::from_names(&table(&[("hard_drop", &["enter", "up"])])).unwrap();
+        let names = bindings.to_names();
+        assert_eq!(names["hard_drop"], ["enter", "up"]);
+        let again = KeyBindings::from_names(&names).unwrap();
+        assert_eq!(again.to_names(), names);
+    }
+
+    #[test]
+    fn rebinding_takes_the_key_from_its_action() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::Hold, Key::Char('q')).unwrap();
+        assert_eq!(bindings.keys(Action::Hold), [Key::Char('q')]);
+        assert_eq!(bindings.keys(Action::Quit), [Key::Esc]);
+        assert_eq!(bindings.action(Key::Char('c')), None);
+        // `w` is the only key of rotate_ccw
+        assert!(bindings.rebind(Action::Pause, Key::Char('w')).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::utils::Ivec2;
+
+use super::{
+    board::Board,
+    types::{Tetrominoe, TetrominoeType},
+};
+
+/// offsets tried in order when a rotation doesn't fit in place, for each starting orientation
+/// (0 is the spawn one, then clockwise). `y` goes down like on the board
+pub type KickTable = [Vec<Ivec2>; 4];
+
+/// names of the starting orientations of a 180° table, as written in the config file
+pub const HALF_TURNS: [&str; 4] = ["0>2", "1>3", "2>0", "3>1"];
+
+/// SRS kicks of the J, L, S, T and Z pieces for a clockwise turn from each orientation, written
+/// with `y` going up as in the SRS tables
+const JLSTZ_CW: [[(isize, isize); 5]; 4] = [
+    [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+    [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+    [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+    [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+];
+/// same for the I piece
+const I_CW: [[(isize, isize); 5]; 4] = [
+    [(0, 0), (-2, 0), (1, 0), (-2, -1), (1, 2)],
+    [(0, 0), (-1, 0), (2, 0), (-1, 2), (2, -1)],
+    [(0, 0), (2, 0), (-1, 0), (2, 1), (-1, -2)],
+    [(0, 0), (1, 0), (-2, 0), (1, -2), (-2, 1)],
+];
+/// SRS+ 180° kicks (the same for every piece), `y` going up
+const SRS_PLUS_180: [[(isize, isize); 6]; 4] = [
+    [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)],
+    [(0, 0), (1, 0), (1, 2), (1, 1), (0, 2), (0, 1)],
+    [(0, 0), (0, -1), (-1, -1), (1, -1), (-1, 0), (1, 0)],
+    [(0, 0), (-1, 0), (-1, 2), (-1, 1), (0, 2), (0, 1)],
+];
+
+/// `rotated`, a piece turned from the `from` orientation, moved by the first kick of its turn
+/// that fits on `board` (`table_180` giving the kicks of half turns), with the index of that kick.
+/// Without a table, pieces only turn in place
+pub fn first_fit(
+    board: &Board,
+    from: usize,
+    rotated: &Tetrominoe,
+    table_180: Option<&KickTable>,
+) -> Option<(Tetrominoe, usize)> {
+    let kicks = match (table_180, (rotated.rotation + 4 - from) % 4) {
+        (None, _) => vec![Ivec2::new(0, 0)],
+        (Some(table_180), 2) => table_180[from].clone(),
+        (Some(_), turn) => srs(rotated.ttype, from, turn == 3),
+    };
+    kicks
+        .into_iter()
+        .map(|kick| rotated.moved(kick.x, kick.y))
+        .enumerate()
+        .find(|(_, kicked)| board.fits(&kicked.cells()))
+        .map(|(i, kicked)| (kicked, i))
+}
+
+/// kicks of a quarter turn from the `from` orientation
+pub fn srs(ttype: TetrominoeType, from: usize, ccw: bool) -> Vec<Ivec2> {
+    let table = match ttype {
+        TetrominoeType::O => return vec![Ivec2::new(0, 0)],
+        TetrominoeType::I => I_CW,
+        _ => JLSTZ_CW,
+    };
+    // a counterclockwise turn kicks the opposite way of the clockwise turn coming back
+    let (row, sign) = match ccw {
+        true => (table[(from + 3) % 4], -1),
+        false => (table[from], 1),
+    };
+    row.iter()
+        .map(|(x, y)| Ivec2::new(sign * x, -sign * y))
+        .collect()
+}
+
+/// offsets written with `y` going up, as board offsets
+pub fn from_y_up(offsets: &[(isize, isize)]) -> Vec<Ivec2> {
+    offsets.iter().map(|(x, y)| Ivec2::new(*x, -y)).collect()
+}
+
+/// inverse of `from_y_up`
+pub fn to_y_up(offsets: &[Ivec2]) -> Vec<(isize, isize)> {
+    offsets.iter().map(|o| (o.x, -o.y)).collect()
+}
+
+/// a 180° table as written in the config and rules files: starting and ending orientations
+/// (`0>2`...) -> kicks as [x, y] with y going up
+pub type YUpTable = BTreeMap<String, Vec<[isize; 2]>>;
+
+/// `table` as written in the files
+pub fn to_y_up_table(table: &KickTable) -> YUpTable {
+    HALF_TURNS
+        .iter()
+        .zip(table)
+        .map(|(turn, offsets)| {
+            let offsets = to_y_up(offsets).into_iter().map(|(x, y)| [x, y]);
+            (turn.to_string(), offsets.collect())
+        })
+        .collect()
+}
+
+/// the table written in a file, the turns left out only turning in place. What's wrong with it
+/// is pushed to `errors`
+pub fn from_y_up_table(written: &YUpTable, errors: &mut Vec<String>) -> KickTable {
+    let mut table = Kicks180::None.table(&Default::default());
+    for (turn, offsets) in written {
+        match HALF_TURNS.iter().position(|t| t == turn) {
+            Some(_) if offsets.is_empty() => {
+                errors.push(format!("no kick given for the `{turn}` 180° rotation"))
+            }
+            Some(i) => {
+                let offsets = offsets.iter().map(|[x, y]| (*x, *y)).collect::<Vec<_>>();
+                table[i] = from_y_up(&offsets);
+            }
+            None => errors.push(format!(
+                "unknown 180° rotation `{turn}`, expected one of {}",
+                HALF_TURNS.join(", ")
+            )),
+        }
+    }
+    table
+}
+
+/// (de)serializes a 180° table as a `YUpTable`, for `#[serde(with = "kicks::y_up")]`
+pub mod y_up {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(table: &KickTable, serializer: S) -> Result<S::Ok, S::Error> {
+        to_y_up_table(table).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<KickTable, D::Error> {
+        let mut errors = vec![];
+        let table = from_y_up_table(&YUpTable::deserialize(deserializer)?, &mut errors);
+        match errors.is_empty() {
+            true => Ok(table),
+            false => Err(serde::de::Error::custom(errors.join(", "))),
+        }
+    }
+}
+
+/// kick table of the 180° rotation
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kicks180 {
+    /// the piece only turns if it fits in place
+    None,
+    SrsPlus,
+    /// `Settings::custom_kicks_180`
+    Custom,
+}
+
+impl Kicks180 {
+    pub const ALL: [Kicks180; 3] = [Kicks180::None, Kicks180::SrsPlus, Kicks180::Custom];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kicks180::None => "none",
+            Kicks180::SrsPlus => "srs+",
+            Kicks180::Custom => "custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Kicks180::ALL.into_iter().find(|k| k.name() == name)
+    }
+
+    /// the table to use, `custom` being the one of the config file
+    pub fn table(&self, custom: &KickTable) -> KickTable {
+        match self {
+            Kicks180::None => std::array::from_fn(|_| vec![Ivec2::new(0, 0)]),
+            Kicks180::SrsPlus => SRS_PLUS_180.map(|row| from_y_up(&row)),
+            Kicks180::Custom => custom.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offsets(kicks: &[Ivec2]) -> Vec<(isize, isize)> {
+        kicks.iter().map(|k| (k.x, k.y)).collect()
+    }
+
+    #[test]
+    fn srs_kicks_are_board_offsets() {
+        // 0>R of the SRS table, (-1, +1) then (0, -2) with y going up
+        assert_eq!(
+            offsets(&srs(TetrominoeType::T, 0, false)),
+            [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)]
+        );
+        // R>0, the 0>R kicks the other way
+        assert_eq!(
+            offsets(&srs(TetrominoeType::S, 1, true)),
+            [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)]
+        );
+        // 0>L, the L>0 kicks the other way
+        assert_eq!(
+            offsets(&srs(TetrominoeType::I, 0, true)),
+            [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)]
+        );
+        assert_eq!(offsets(&srs(TetrominoeType::O, 2, false)), [(0, 0)]);
+    }
+
+    #[test]
+    fn srs_plus_180_kicks() {
+        let table = Kicks180::SrsPlus.table(&Default::default());
+        assert_eq!(
+            offsets(&table[0]),
+            [(0, 0), (0, -1), (1, -1), (-1, -1), (1, 0), (-1, 0)]
+        );
+        assert_eq!(to_y_up(&table[1]), SRS_PLUS_180[1]);
+        assert_eq!(
+            Kicks180::None
+                .table(&Default::default())
+                .map(|row| offsets(&row)),
+            [[(0, 0)]; 4].map(|row| row.to_vec())
+        );
+    }
+}
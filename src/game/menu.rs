@@ -0,0 +1,286 @@
+use super::{mode::GameMode, puzzle::Puzzle};
+
+/// an entry of a vertical menu
+pub trait MenuEntry: Copy + PartialEq {
+    fn label(&self) -> &'static str;
+    /// items whose screen doesn't exist yet are shown but can't be selected
+    fn enabled(&self) -> bool {
+        true
+    }
+}
+
+pub struct MenuList<T: MenuEntry> {
+    pub items: Vec<T>,
+    pub selected: usize,
+}
+
+impl<T: MenuEntry> MenuList<T> {
+    pub fn new(items: Vec<T>) -> Self {
+        Self { items, selected: 0 }
+    }
+
+    pub fn item(&self) -> T {
+        self.items[self.selected]
+    }
+
+    /// moves the cursor by `step` items (wrapping), skipping disabled ones
+    pub fn move_cursor(&mut self, step: isize) {
+        let n = self.items.len() as isize;
+        loop {
+            self.selected = (self.selected as isize + step).rem_euclid(n) as usize;
+            if self.item().enabled() {
+                break;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MenuItem {
+    Play,
+    Settings,
+    HighScores,
+    Replays,
+    Quit,
+}
+
+impl MenuEntry for MenuItem {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuItem::Play => "PLAY",
+            MenuItem::Settings => "SETTINGS",
+            MenuItem::HighScores => "HIGH SCORES",
+            MenuItem::Replays => "REPLAYS",
+            MenuItem::Quit => "QUIT",
+        }
+    }
+}
+
+/// title screen menu
+pub struct Menu {
+    pub list: MenuList<MenuItem>,
+    pub mode: usize,
+    /// puzzles of the PUZZLE mode, the one read from a file (if any) first
+    pub puzzles: Vec<Puzzle>,
+    pub puzzle: usize,
+}
+
+impl Menu {
+    /// `mode` is the one selected first (the first one if `None`), `puzzle` a puzzle read from
+    /// a file to offer before the bundled ones
+    pub fn new(mode: Option<GameMode>, puzzle: Option<Puzzle>) -> Self {
+        Self {
+            list: MenuList::new(vec![
+                MenuItem::Play,
+                MenuItem::Settings,
+                MenuItem::HighScores,
+                MenuItem::Replays,
+                MenuItem::Quit,
+            ]),
+            mode: mode
+                .and_then(|mode| GameMode::ALL.iter().position(|m| *m == mode))
+                .unwrap_or(0),
+            puzzles: puzzle.into_iter().chain(Puzzle::bundled()).collect(),
+            puzzle: 0,
+        }
+    }
+
+    pub fn item(&self) -> MenuItem {
+        self.list.item()
+    }
+
+    pub fn mode(&self) -> GameMode {
+        GameMode::ALL[self.mode]
+    }
+
+    pub fn cycle_mode(&mut self, step: isize) {
+        let n = GameMode::ALL.len() as isize;
+        self.mode = (self.mode as isize + step).rem_euclid(n) as usize;
+    }
+
+    /// name of the selected mode, with the number of the puzzle
+    pub fn mode_label(&self) -> String {
+        match self.mode() {
+            GameMode::Puzzle => format!("PUZZLE {}", self.puzzle + 1),
+            mode => mode.name().to_string(),
+        }
+    }
+
+    pub fn puzzle(&self) -> &Puzzle {
+        &self.puzzles[self.puzzle]
+    }
+
+    /// `cycle_mode` going through every puzzle on the way, as if each one was a mode
+    pub fn cycle_play(&mut self, step: isize) {
+        let puzzle = self.puzzle as isize + step;
+        if self.mode() == GameMode::Puzzle && (0..self.puzzles.len() as isize).contains(&puzzle) {
+            self.puzzle = puzzle as usize;
+            return;
+        }
+        self.cycle_mode(step);
+        if self.mode() == GameMode::Puzzle {
+            self.puzzle = if step > 0 { 0 } else { self.puzzles.len() - 1 };
+        }
+    }
+
+    /// whether a puzzle comes after the selected one
+    pub fn has_next_puzzle(&self) -> bool {
+        self.mode() == GameMode::Puzzle && self.puzzle + 1 < self.puzzles.len()
+    }
+
+    pub fn next_puzzle(&mut self) {
+        if self.has_next_puzzle() {
+            self.puzzle += 1;
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GameOverItem {
+    NextPuzzle,
+    Restart,
+    Menu,
+    SaveReplay,
+    Quit,
+}
+
+impl MenuEntry for GameOverItem {
+    fn label(&self) -> &'static str {
+        match self {
+            GameOverItem::NextPuzzle => "NEXT PUZZLE",
+            GameOverItem::Restart => "RESTART",
+            GameOverItem::Menu => "MENU",
+            GameOverItem::SaveReplay => "SAVE REPLAY",
+            GameOverItem::Quit => "QUIT",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PauseItem {
+    Resume,
+    Restart,
+    Settings,
+    KeyBindings,
+    QuitToMenu,
+}
+
+impl MenuEntry for PauseItem {
+    fn label(&self) -> &'static str {
+        match self {
+            PauseItem::Resume => "RESUME",
+            PauseItem::Restart => "RESTART",
+            PauseItem::Settings => "SETTINGS",
+            PauseItem::KeyBindings => "KEY BINDINGS",
+            PauseItem::QuitToMenu => "QUIT TO MENU",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SettingItem {
+    Das,
+    Arr,
+    SoftDropFactor,
+    Ghost,
+    NextCount,
+    Theme,
+    Fps,
+    AutoResume,
+    PartialLockOut,
+    Kicks180,
+    Rules,
+    Are,
+    Irs,
+    LineClearDelay,
+    ClearAnimation,
+    Messiness,
+    ZenGravity,
+    KeyBindings,
+    Back,
+}
+
+impl MenuEntry for SettingItem {
+    fn label(&self) -> &'static str {
+        match self {
+            SettingItem::Das => "DAS",
+            SettingItem::Arr => "ARR",
+            SettingItem::SoftDropFactor => "SOFT DROP",
+            SettingItem::Ghost => "GHOST",
+            SettingItem::NextCount => "NEXT PIECES",
+            SettingItem::Theme => "THEME",
+            SettingItem::Fps => "FPS CAP",
+            SettingItem::AutoResume => "AUTO RESUME",
+            SettingItem::PartialLockOut => "LOCK OUT",
+            SettingItem::Kicks180 => "180 KICKS",
+            SettingItem::Rules => "RULES",
+            SettingItem::Are => "ENTRY DELAY",
+            SettingItem::Irs => "IRS/IHS",
+            SettingItem::LineClearDelay => "CLEAR DELAY",
+            SettingItem::ClearAnimation => "CLEAR ANIMATION",
+            SettingItem::Messiness => "GARBAGE MESS",
+            SettingItem::ZenGravity => "ZEN GRAVITY",
+            SettingItem::KeyBindings => "KEY BINDINGS",
+            SettingItem::Back => "BACK",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Item {
+        A,
+        Disabled,
+        B,
+    }
+
+    impl MenuEntry for Item {
+        fn label(&self) -> &'static str {
+            "ITEM"
+        }
+
+        fn enabled(&self) -> bool {
+            *self != Item::Disabled
+        }
+    }
+
+    #[test]
+    fn the_cursor_skips_disabled_items() {
+        let mut list = MenuList::new(vec![Item::A, Item::Disabled, Item::B]);
+        list.move_cursor(1);
+        assert_eq!(list.item(), Item::B);
+        list.move_cursor(1);
+        assert_eq!(list.item(), Item::A);
+        list.move_cursor(-1);
+        assert_eq!(list.item(), Item::B);
+    }
+
+    #[test]
+    fn modes_wrap_around() {
+        let mut menu = Menu::new(None, None);
+        menu.cycle_mode(-1);
+        assert!(menu.mode() == GameMode::ALL[GameMode::ALL.len() - 1]);
+        menu.cycle_mode(1);
+        assert!(menu.mode() == GameMode::ALL[0]);
+    }
+
+    #[test]
+    fn puzzles_are_played_one_after_the_other() {
+        let puzzle = GameMode::ALL.iter().position(|m| *m == GameMode::Puzzle);
+        let mut menu = Menu::new(Some(GameMode::ALL[puzzle.unwrap() - 1]), None);
+        menu.cycle_play(1);
+        assert_eq!(menu.mode_label(), "PUZZLE 1");
+        let count = menu.puzzles.len();
+        for _ in 1..count {
+            assert!(menu.has_next_puzzle());
+            menu.next_puzzle();
+        }
+        assert_eq!(menu.mode_label(), format!("PUZZLE {count}"));
+        assert!(!menu.has_next_puzzle());
+        menu.cycle_play(-1);
+        assert_eq!(menu.puzzle, count - 2);
+    }
+}
@@ -1,225 +1,931 @@
 use std::{
-    collections::HashMap,
+    path::PathBuf,
     process::Command,
     thread,
     time::{Duration, Instant},
     vec,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use board::TopOut;
+use bot::Bot;
 use graphics::GameGraphics;
-use termion::{event::Key, input::TermRead};
-use types::Tetrominoe;
+use input::{Action, AutoShift, Input, InputListener, KeyBindings};
+use menu::{GameOverItem, Menu, MenuItem, MenuList, PauseItem, SettingItem};
+use mode::{Ending, GameMode};
+use nanorand::{Rng, WyRand};
+use puzzle::Puzzle;
+use replay::Replay;
+use scores::{HighScores, ScoreEntry};
+use state::GameState;
+use termion::event::Key;
+use theme::Theme;
 
-use crate::utils::{Uvec2, SGR};
+use crate::utils::{unix_time, SGR};
+use settings::Settings;
 
+mod board;
+mod bot;
+mod finesse;
+mod font;
 mod graphics;
+mod input;
+mod kicks;
+mod menu;
+pub mod mode;
+pub mod puzzle;
+pub mod replay;
+pub mod rules;
+pub mod scores;
+pub mod settings;
+mod state;
+mod stats;
+pub mod theme;
+mod tspin;
 mod types;
 
-const FPS: usize = 30;
+/// game logic rate, independent of the rendering rate (`Settings::fps`)
+pub const TICKS_PER_SECOND: usize = 60;
+/// ticks a piece can stay on the ground before locking
+const LOCK_DELAY_TICKS: usize = 30;
+/// number of times moving a piece on the ground can restart its lock delay
+const MAX_LOCK_RESETS: usize = 15;
+/// ticks a T-spin callout stays on screen
+const CALLOUT_TICKS: u64 = 90;
+/// number of next pieces generated ahead, `Settings::next_count` of them are shown
+const NEXT_QUEUE: usize = 4;
 const BACKGROUD_COLOR: SGR = SGR::BlackBG;
 
+/// what a terminal cell shows: background color and text (character, foreground color)
+type Cell = (SGR, Option<(char, SGR)>);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Screen {
+    Menu,
+    Playing,
+    Paused,
+    GameOver,
+    HighScores,
+    Replays,
+    Settings,
+    KeyBindings,
+}
+
+/// what to do right away instead of showing the title screen
+pub enum Start {
+    Play(GameMode),
+    Replay(Box<Replay>),
+    Bot(GameMode),
+}
+
+/// options of the session given on the command line
+#[derive(Default)]
+pub struct Launch {
+    pub start: Option<Start>,
+    /// mode selected on the title screen
+    pub mode: Option<GameMode>,
+    /// every game of the session uses this seed instead of a random one
+    pub seed: Option<u64>,
+    pub level: Option<usize>,
+    /// puzzle read from a file, offered before the bundled ones
+    pub puzzle: Option<Puzzle>,
+    /// board size in blocks, overrides the settings
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    /// the replay of each finished game gets written there
+    pub record: Option<PathBuf>,
+    /// color theme of the session, overrides the settings without being saved
+    pub theme: Option<Theme>,
+    pub no_color: bool,
+}
+
+/// who plays the current game
+pub enum Control {
+    Player,
+    /// actions come from a replay, `next` being the index of the next event to play
+    Replay {
+        replay: Box<Replay>,
+        next: usize,
+    },
+    Bot(Bot),
+}
+
+/// rows waiting to be removed during the line clear delay
+#[derive(Clone)]
+pub struct LineClear {
+    /// full rows, top to bottom
+    pub rows: Vec<usize>,
+    /// ticks elapsed since the piece locked
+    pub ticks: usize,
+}
+
+/// what's shown on the game over screen
+pub struct GameOverState {
+    pub list: MenuList<GameOverItem>,
+    pub ending: Ending,
+    pub new_best: bool,
+    /// best result of the mode before this game, to compare with
+    pub previous_best: Option<ScoreEntry>,
+    /// feedback of the last actions (score or replay saving), one line each
+    pub messages: Vec<String>,
+}
+
 pub struct GameManager {
     graphics: GameGraphics,
     cells: Vec<Vec<SGR>>,
-    cols_borders: Vec<usize>,
+    /// text layer drawn over `cells` (character and its foreground color)
+    texts: Vec<Vec<Option<(char, SGR)>>>,
+
+    /// the game being played, apart from how it's shown and controlled
+    game: GameState,
+    /// the game as each placed piece spawned, to undo placements (Zen only), and the undone ones
+    undo: Vec<GameState>,
+    redo: Vec<GameState>,
+    /// the game as the falling piece spawned, `None` until the next piece spawns once it locked
+    spawned: Option<GameState>,
+    /// held move keys (DAS/ARR)
+    autoshift: AutoShift,
+    /// held soft drop key, repeating `soft_drop_factor` times faster than gravity
+    soft_drop: AutoShift,
+
+    control: Control,
+    launch: Launch,
+    high_scores: HighScores,
 
-    tetrominoe: Tetrominoe,
-    next_tetrominoes: Vec<Tetrominoe>,
+    replay: Replay,
 
-    pause: bool,
+    screen: Screen,
+    menu: Menu,
+    pause_menu: MenuList<PauseItem>,
+    game_over: GameOverState,
+    /// replay files listed on the replays screen, newest first
+    replay_files: Vec<PathBuf>,
+    replays_selected: usize,
+    /// feedback of the replays screen (listing or loading errors)
+    replays_message: Option<String>,
+    /// whether the current pause was triggered by the terminal losing focus
+    focus_paused: bool,
+    settings: Settings,
+    /// where settings changes get written, `None` when there's no config directory
+    config_path: Option<PathBuf>,
+    settings_menu: MenuList<SettingItem>,
+    /// screen shown when leaving the settings screen
+    settings_return: Screen,
+    /// selected row of the key bindings screen (actions then BACK)
+    bindings_selected: usize,
+    /// action waiting for its new key
+    binding_capture: Option<Action>,
+    /// screen shown when leaving the key bindings screen
+    bindings_return: Screen,
+    /// feedback of the settings screens (saving or rebinding errors)
+    settings_message: Option<String>,
+    /// what's currently on the terminal, only the cells that changed get repainted
+    rendered: Vec<Vec<Option<Cell>>>,
 }
 
 impl GameManager {
-    pub fn init() -> Self {
-        let graphics = GameGraphics::init();
+    pub fn init(settings: Settings, config_path: Option<PathBuf>, launch: Launch) -> Result<Self> {
+        let (width, height) = (
+            launch.width.unwrap_or(settings.width),
+            launch.height.unwrap_or(settings.height),
+        );
+        let graphics = GameGraphics::init(width, height)?;
         let cells = vec![
             vec![BACKGROUD_COLOR; graphics.term_size.cols as usize];
             graphics.term_size.rows as usize
         ];
 
-        let mut s = Self {
+        let texts = vec![vec![None; cells[0].len()]; cells.len()];
+        let rendered = vec![vec![None; cells[0].len()]; cells.len()];
+
+        let replay = Replay::new(GameMode::Marathon, 0, 1, width, height);
+        Ok(Self {
             cells,
-            tetrominoe: Tetrominoe::new(&graphics.inner_box_size, graphics.scale, None, None),
-            next_tetrominoes: vec![],
-            pause: false,
-            cols_borders: vec![
-                graphics.box_size.rows as usize - 1;
-                graphics.inner_box_size.cols as usize
-            ],
+            texts,
+            game: GameState::new(&replay),
+            undo: vec![],
+            redo: vec![],
+            spawned: None,
+            autoshift: AutoShift::default(),
+            soft_drop: AutoShift::default(),
+            control: Control::Player,
+            high_scores: HighScores::load(),
+            replay,
+            screen: Screen::Menu,
+            menu: Menu::new(launch.mode, launch.puzzle.clone()),
+            pause_menu: MenuList::new(vec![
+                PauseItem::Resume,
+                PauseItem::Restart,
+                PauseItem::Settings,
+                PauseItem::KeyBindings,
+                PauseItem::QuitToMenu,
+            ]),
+            game_over: GameOverState {
+                list: MenuList::new(vec![]),
+                ending: Ending::TopOut(TopOut::Block),
+                new_best: false,
+                previous_best: None,
+                messages: vec![],
+            },
+            replay_files: vec![],
+            replays_selected: 0,
+            replays_message: None,
+            focus_paused: false,
+            settings,
+            config_path,
+            settings_menu: MenuList::new(vec![
+                SettingItem::Rules,
+                SettingItem::Das,
+                SettingItem::Arr,
+                SettingItem::SoftDropFactor,
+                SettingItem::Ghost,
+                SettingItem::NextCount,
+                SettingItem::Theme,
+                SettingItem::Fps,
+                SettingItem::AutoResume,
+                SettingItem::PartialLockOut,
+                SettingItem::Kicks180,
+                SettingItem::Are,
+                SettingItem::Irs,
+                SettingItem::LineClearDelay,
+                SettingItem::ClearAnimation,
+                SettingItem::Messiness,
+                SettingItem::ZenGravity,
+                SettingItem::KeyBindings,
+                SettingItem::Back,
+            ]),
+            settings_return: Screen::Menu,
+            bindings_selected: 0,
+            binding_capture: None,
+            bindings_return: Screen::Settings,
+            settings_message: None,
+            rendered,
+            launch,
             graphics,
+        })
+    }
+
+    /// resets the board and starts a new game, played by the same player as the previous one
+    /// (a replay starts over)
+    pub fn new_game(&mut self, mode: GameMode) {
+        self.start_game(mode, None)
+    }
+
+    /// starts the current game over with the same seed, so with the same pieces
+    fn retry_game(&mut self) {
+        self.start_game(self.game.mode, Some(self.replay.seed))
+    }
+
+    /// `new_game` with this seed instead of the one given on the command line or a random one
+    fn start_game(&mut self, mode: GameMode, seed: Option<u64>) {
+        // the title screen tells why
+        let replaying = matches!(self.control, Control::Replay { .. });
+        if mode == GameMode::Puzzle && !replaying && !self.puzzle_fits() {
+            return self.open_menu();
+        }
+        self.clear_screen();
+        self.draw_tetris_box();
+        self.draw_panels();
+
+        // the rules and options of the game, taken from the replay or the settings
+        let replay = match &mut self.control {
+            Control::Replay { replay, next } => {
+                *next = 0;
+                Replay {
+                    events: vec![],
+                    ..(**replay).clone()
+                }
+            }
+            control => {
+                if let Control::Bot(bot) = control {
+                    *bot = Bot::default();
+                }
+                let seed = seed
+                    .or(self.launch.seed)
+                    .unwrap_or_else(|| WyRand::new().generate::<u64>());
+                let (width, height) = self.board_size();
+                let settings = &self.settings;
+                let preset = mode.rules().unwrap_or(settings.rules);
+                let mut rules = preset.rule_set(settings);
+                // pieces only fall when dropped
+                if (mode == GameMode::Zen && !settings.zen_gravity) || mode == GameMode::Puzzle {
+                    rules.gravity = vec![(0, 0, 1)];
+                }
+                let level = mode.start_level(self.launch.level, rules.scoring);
+                Replay {
+                    rules,
+                    preset,
+                    messiness: settings.messiness,
+                    puzzle: (mode == GameMode::Puzzle).then(|| self.menu.puzzle().clone()),
+                    ..Replay::new(mode, seed, level, width, height)
+                }
+            }
         };
+        self.game = GameState::new(&replay);
+        self.replay = replay;
+        self.undo.clear();
+        self.redo.clear();
+        self.spawned = None;
+        self.update_history();
+        self.autoshift.reset();
+        self.soft_drop.reset();
+        // replays and the bot don't press keys, `handle_game_key` counts the player's presses
+        self.game.judges_finesse = matches!(self.control, Control::Player);
+        self.screen = Screen::Playing;
+    }
 
-        s.next_tetrominoes = (0..3)
-            .map(|i| Tetrominoe::new(&s.graphics.inner_box_size, 1, None, Some(s.nt_pos(i))))
-            .collect();
-        s
+    /// hides the board behind the pause overlay, the game doesn't tick until it's resumed
+    fn pause_game(&mut self) {
+        self.pause_menu.selected = 0;
+        self.focus_paused = false;
+
+        self.clear_screen();
+        self.draw_tetris_box();
+        self.screen = Screen::Paused;
     }
 
-    pub fn pick_next_tetrominoe(&mut self) {
-        let new_tetrominoe = self.next_tetrominoes.remove(0);
-        self.tetrominoe = Tetrominoe::from_self(&new_tetrominoe, Some(self.graphics.scale), None);
+    /// puts the board back
+    fn resume_game(&mut self) {
+        // settings may have changed during the pause
+        self.clear_screen();
+        self.draw_tetris_box();
+        self.draw_panels();
+        self.focus_paused = false;
+        self.screen = Screen::Playing;
+    }
+
+    /// saves the score (of the player only) and shows the game over screen
+    fn end_game(&mut self, ending: Ending) {
+        let previous_best = self
+            .high_scores
+            .top(self.game.mode, self.replay.preset)
+            .first()
+            .cloned()
+            .cloned();
+        let mut messages = vec![];
+        let new_best = match self.control {
+            Control::Player if self.game.mode.is_ranked(ending) => {
+                let entry = ScoreEntry {
+                    mode: self.game.mode.name().to_string(),
+                    rules: self.replay.preset.name().to_string(),
+                    score: self.game.stats.score,
+                    lines: self.game.stats.lines,
+                    level: self.game.stats.level,
+                    time: self.game.stats.elapsed(),
+                    date: unix_time(),
+                    splits: self.game.stats.splits.clone(),
+                };
+                self.high_scores.submit(entry).unwrap_or_else(|why| {
+                    messages.push(format!("couldn't save score: {why}"));
+                    false
+                })
+            }
+            _ => false,
+        };
+        // a score that couldn't be saved doesn't prevent recording the game
+        if let Some(path) = &self.launch.record {
+            messages.push(match self.replay.save_to(path) {
+                Ok(()) => format!("replay saved to {}", path.display()),
+                Err(why) => format!("couldn't save replay: {why}"),
+            })
+        }
 
-        for i in 0..=1 {
-            self.next_tetrominoes[i] =
-                Tetrominoe::from_self(&self.next_tetrominoes[i], None, Some(self.nt_pos(i)))
+        let mut items = vec![
+            GameOverItem::Restart,
+            GameOverItem::Menu,
+            GameOverItem::SaveReplay,
+            GameOverItem::Quit,
+        ];
+        // on to the next puzzle once one is solved
+        let player = matches!(self.control, Control::Player);
+        if player && ending == Ending::Finished && self.menu.has_next_puzzle() {
+            items.insert(0, GameOverItem::NextPuzzle);
         }
-        self.next_tetrominoes.push(Tetrominoe::new(
-            &self.graphics.inner_box_size,
-            1,
-            None,
-            Some(self.nt_pos(2)),
-        ));
+        self.game_over = GameOverState {
+            list: MenuList::new(items),
+            ending,
+            new_best,
+            previous_best,
+            messages,
+        };
+        self.clear_screen();
+        self.draw_tetris_box();
+        self.screen = Screen::GameOver;
     }
-    /// helper to compute next tetrominoes position according to its rank
-    fn nt_pos(&self, rank: usize) -> Uvec2 {
-        let ix = self.graphics.inner_box_size.cols as usize;
-        let bx = self.graphics.inner_box_size.cols as usize;
-        let x = ix + bx / 9;
-        match rank {
-            0 => Uvec2::new(x, 1),
-            1 => Uvec2::new(x, 6),
-            2 => Uvec2::new(x, 11),
-            _ => unreachable!(),
+
+    /// leaves the current game (if any) and shows the title screen
+    pub fn open_menu(&mut self) {
+        self.control = Control::Player;
+        self.clear_screen();
+        self.draw_tetris_box();
+        self.screen = Screen::Menu;
+    }
+
+    /// lists the saved replays, the selected one gets watched
+    fn open_replays(&mut self) {
+        (self.replay_files, self.replays_message) = match Replay::list() {
+            Ok(files) if files.is_empty() => (files, Some("no replay yet".to_string())),
+            Ok(files) => (files, None),
+            Err(why) => (vec![], Some(format!("couldn't list replays: {why}"))),
+        };
+        self.replays_selected = 0;
+        self.clear_screen();
+        self.draw_tetris_box();
+        self.screen = Screen::Replays;
+    }
+
+    fn open_settings(&mut self, from: Screen) {
+        self.settings_menu.selected = 0;
+        self.settings_message = None;
+        self.settings_return = from;
+        self.clear_screen();
+        self.draw_tetris_box();
+        self.screen = Screen::Settings;
+    }
+
+    fn open_key_bindings(&mut self, from: Screen) {
+        self.bindings_selected = 0;
+        self.binding_capture = None;
+        self.settings_message = None;
+        self.bindings_return = from;
+        self.clear_screen();
+        self.draw_tetris_box();
+        self.screen = Screen::KeyBindings;
+    }
+
+    /// goes back to the screen the settings were opened from, redrawn with the new theme
+    fn leave_settings(&mut self, to: Screen) {
+        match to {
+            Screen::Menu => self.open_menu(),
+            to => {
+                self.clear_screen();
+                self.draw_tetris_box();
+                self.screen = to;
+            }
         }
     }
 
+    /// writes the settings to the config file, they're already applied to the running game
+    fn save_settings(&mut self) {
+        self.settings_message = match &self.config_path {
+            Some(path) => self
+                .settings
+                .save(path)
+                .err()
+                .map(|why| format!("couldn't save settings: {why}")),
+            None => Some("no config directory, settings won't be kept".to_string()),
+        };
+    }
+
+    /// color theme the game is drawn with
+    fn theme(&self) -> Theme {
+        self.launch.theme.unwrap_or(self.settings.theme)
+    }
+
+    /// board width and height in blocks
+    fn board_size(&self) -> (usize, usize) {
+        (
+            self.launch.width.unwrap_or(self.settings.width),
+            self.launch.height.unwrap_or(self.settings.height),
+        )
+    }
+
+    /// whether the puzzle selected on the title screen can be played on the board
+    fn puzzle_fits(&self) -> bool {
+        let (width, height) = self.board_size();
+        self.menu.puzzle().fits(width, height)
+    }
+
     pub fn start(&mut self) -> Result<()> {
         // init terminal screen to clean everything to start drawing
         self.graphics.hide_cursor()?;
+        self.graphics.enable_focus_reporting()?;
         self.graphics.clear()?;
         self.graphics.clear_history()?;
         self.graphics.move_cursor(1, 1)?;
 
-        self.draw_tetris_box();
-        self.graphics.apply()?;
+        match self.launch.start.take() {
+            Some(Start::Play(mode)) => self.new_game(mode),
+            Some(Start::Replay(replay)) => {
+                let mode = replay.mode;
+                self.control = Control::Replay { replay, next: 0 };
+                self.new_game(mode)
+            }
+            Some(Start::Bot(mode)) => {
+                self.control = Control::Bot(Bot::default());
+                self.new_game(mode)
+            }
+            None => self.open_menu(),
+        }
 
         // Input event listener init
-        let mut key_listener = termion::async_stdin().keys();
+        let mut input_listener = InputListener::init();
 
-        // game loop
+        // game loop, the screen is only repainted every `TICKS_PER_SECOND / fps` ticks
+        let mut frame = 0;
         loop {
             let now = Instant::now();
 
             // Read input (if any)
-            let input = key_listener.next();
-            if let Some(Ok(key)) = input {
-                match key {
-                    Key::Esc | Key::Char('q') => break,
-                    Key::Char('w') if !self.pause => {
-                        self.clear_tetrominoe();
-                        self.tetrominoe.rotate(true)
-                    }
-                    Key::Char('e') if !self.pause => {
-                        self.clear_tetrominoe();
-                        self.tetrominoe.rotate(false)
-                    }
-                    Key::Left | Key::Char('a') if !self.pause => {
-                        self.clear_tetrominoe();
-                        self.tetrominoe.translate_left()
+            let quit = match input_listener.poll() {
+                Some(input) => self.handle_input(input),
+                None => false,
+            };
+            if quit {
+                break;
+            }
+
+            match self.screen {
+                Screen::Menu => self.draw_menu(),
+                Screen::Playing => {
+                    match self.control {
+                        Control::Player => self.update_held_inputs(),
+                        Control::Replay { .. } => self.play_replay_events(),
+                        Control::Bot(_) => self.play_bot_move(),
                     }
-                    Key::Right | Key::Char('d') if !self.pause => {
-                        self.clear_tetrominoe();
-                        self.tetrominoe.translate_right()
+                    let ending = self.game.compute_next_frame();
+                    self.game.tick += 1;
+                    self.update_history();
+
+                    if let Some(ending) = ending {
+                        self.end_game(ending);
+                        self.draw_game_over();
+                    } else {
+                        // the hud clears the text layer, the playfield adds the panel labels
+                        self.draw_hud();
+                        self.draw_playfield();
                     }
-                    Key::Char('p') => self.pause = !self.pause,
-                    _ => {}
                 }
+                Screen::GameOver => self.draw_game_over(),
+                Screen::HighScores => self.draw_high_scores(),
+                Screen::Replays => self.draw_replays(),
+                Screen::Paused => self.draw_pause(),
+                Screen::Settings => self.draw_settings(),
+                Screen::KeyBindings => self.draw_key_bindings(),
             }
-
-            if !self.pause {
-                let is_game_over = self.compute_next_frame();
-                if is_game_over {
-                    break;
-                }
-
+            if frame % (TICKS_PER_SECOND / self.settings.fps) == 0 {
                 self.render()?;
                 self.graphics.apply()?;
-                // add optinal text to screen (score, time, title) --> this should be after self.render()
             }
+            frame += 1;
 
+            let tick_duration = Duration::from_secs(1) / TICKS_PER_SECOND as u32;
             let elapsed = now.elapsed();
-            if elapsed < Duration::from_millis(1000 / FPS as u64) {
-                thread::sleep(Duration::from_millis(1000 / FPS as u64) - elapsed)
+            if elapsed < tick_duration {
+                thread::sleep(tick_duration - elapsed)
             }
         }
 
         self.graphics.clear()?;
         self.graphics.clear_history()?;
+        self.graphics.disable_focus_reporting()?;
         self.graphics.show_cursor()?;
         self.graphics.apply()?;
         Command::new("clear").spawn()?;
         Ok(())
     }
 
-    pub fn is_collision(&self) -> bool {
-        for vp in &self.tetrominoe.vertices_pos {
-            let gapscale = self.graphics.scale - 1;
-            let ny = vp.y + gapscale;
-            if ny >= self.cols_borders[vp.x] {
-                return true;
+    /// dispatches an input to the current screen, returns true if the player wants to quit
+    fn handle_input(&mut self, input: Input) -> bool {
+        match input {
+            // auto-pause when switching window/pane, only auto-resume a pause we triggered ourselves
+            Input::FocusOut if self.screen == Screen::Playing => {
+                self.pause_game();
+                self.focus_paused = true;
             }
+            Input::FocusIn if self.focus_paused => {
+                self.focus_paused = false;
+                if self.settings.auto_resume && self.screen == Screen::Paused {
+                    self.resume_game()
+                }
+            }
+            Input::Key(key) => match self.screen {
+                Screen::Menu => return self.handle_menu_key(key),
+                Screen::Playing => self.handle_game_key(key),
+                Screen::GameOver => return self.handle_game_over_key(key),
+                Screen::HighScores => match key {
+                    Key::Left | Key::Char('a') | Key::Char('h') => self.menu.cycle_mode(-1),
+                    Key::Right | Key::Char('d') | Key::Char('l') => self.menu.cycle_mode(1),
+                    Key::Esc | Key::Char('q') | Key::Char('\n') => self.open_menu(),
+                    _ => {}
+                },
+                Screen::Replays => self.handle_replays_key(key),
+                Screen::Paused => self.handle_pause_key(key),
+                Screen::Settings => self.handle_settings_key(key),
+                Screen::KeyBindings => self.handle_key_bindings_key(key),
+            },
+            _ => {}
         }
         false
     }
 
-    pub fn log_new_border(&mut self) -> Result<()> {
-        let mut group_cols: HashMap<usize, Vec<usize>> = HashMap::new();
-        for vp in &self.tetrominoe.vertices_pos {
-            group_cols
-                .entry(vp.x)
-                .and_modify(|rows| rows.push(vp.y))
-                .or_insert(vec![vp.y]);
+    fn handle_menu_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::Esc | Key::Char('q') => return true,
+            Key::Up | Key::Char('w') | Key::Char('k') => self.menu.list.move_cursor(-1),
+            Key::Down | Key::Char('s') | Key::Char('j') => self.menu.list.move_cursor(1),
+            Key::Left | Key::Char('a') | Key::Char('h') if self.menu.item() == MenuItem::Play => {
+                self.menu.cycle_play(-1)
+            }
+            Key::Right | Key::Char('d') | Key::Char('l') if self.menu.item() == MenuItem::Play => {
+                self.menu.cycle_play(1)
+            }
+            Key::Char('\n') | Key::Char(' ') => match self.menu.item() {
+                MenuItem::Play => self.new_game(self.menu.mode()),
+                MenuItem::Settings => self.open_settings(Screen::Menu),
+                MenuItem::HighScores => {
+                    self.clear_texts();
+                    self.screen = Screen::HighScores
+                }
+                MenuItem::Replays => self.open_replays(),
+                MenuItem::Quit => return true,
+            },
+            _ => {}
         }
-        for (col, rows) in group_cols {
-            let highest_y = *rows.iter().min().ok_or(anyhow!(""))?;
+        false
+    }
 
-            let gapscale = self.graphics.scale - 1;
-            for j in 0..gapscale {
-                if self.cols_borders[col + j] > highest_y {
-                    self.cols_borders[col + j] = highest_y;
+    fn handle_replays_key(&mut self, key: Key) {
+        let n = self.replay_files.len().max(1);
+        match key {
+            Key::Up | Key::Char('w') | Key::Char('k') => {
+                self.replays_selected = (self.replays_selected + n - 1) % n
+            }
+            Key::Down | Key::Char('s') | Key::Char('j') => {
+                self.replays_selected = (self.replays_selected + 1) % n
+            }
+            Key::Esc | Key::Char('q') => self.open_menu(),
+            Key::Char('\n') | Key::Char(' ') => {
+                let Some(path) = self.replay_files.get(self.replays_selected) else {
+                    return;
+                };
+                match Replay::load(path) {
+                    Ok(replay) => {
+                        let mode = replay.mode;
+                        let replay = Box::new(replay);
+                        self.control = Control::Replay { replay, next: 0 };
+                        self.new_game(mode)
+                    }
+                    Err(why) => self.replays_message = Some(why.to_string()),
                 }
             }
+            _ => {}
         }
+    }
 
-        Ok(())
+    fn handle_pause_key(&mut self, key: Key) {
+        match key {
+            Key::Up | Key::Char('w') | Key::Char('k') => self.pause_menu.move_cursor(-1),
+            Key::Down | Key::Char('s') | Key::Char('j') => self.pause_menu.move_cursor(1),
+            Key::Char('\n') | Key::Char(' ') => match self.pause_menu.item() {
+                PauseItem::Resume => self.resume_game(),
+                PauseItem::Restart => self.new_game(self.game.mode),
+                PauseItem::Settings => self.open_settings(Screen::Paused),
+                PauseItem::KeyBindings => self.open_key_bindings(Screen::Paused),
+                PauseItem::QuitToMenu => self.open_menu(),
+            },
+            key => match self.settings.keys.action(key) {
+                Some(Action::Pause) => self.resume_game(),
+                Some(Action::Restart) => self.new_game(self.game.mode),
+                Some(Action::Quit) => self.open_menu(),
+                _ => {}
+            },
+        }
     }
 
-    pub fn check_row_clear(&mut self) {
-        for row in self.cells.iter_mut() {
-            if row.iter().all(|c| c != &BACKGROUD_COLOR) {
-                for c in row {
-                    *c = BACKGROUD_COLOR
+    fn handle_settings_key(&mut self, key: Key) {
+        match key {
+            Key::Up | Key::Char('w') | Key::Char('k') => self.settings_menu.move_cursor(-1),
+            Key::Down | Key::Char('s') | Key::Char('j') => self.settings_menu.move_cursor(1),
+            Key::Left | Key::Char('a') | Key::Char('h') => self.change_setting(-1),
+            Key::Right | Key::Char('d') | Key::Char('l') => self.change_setting(1),
+            Key::Esc | Key::Char('q') => self.leave_settings(self.settings_return),
+            Key::Char('\n') | Key::Char(' ') => match self.settings_menu.item() {
+                SettingItem::KeyBindings => self.open_key_bindings(Screen::Settings),
+                SettingItem::Back => self.leave_settings(self.settings_return),
+                _ => self.change_setting(1),
+            },
+            _ => {}
+        }
+    }
+
+    fn change_setting(&mut self, step: isize) {
+        let item = self.settings_menu.item();
+        if matches!(item, SettingItem::KeyBindings | SettingItem::Back) {
+            return;
+        }
+        self.settings.adjust(item, step);
+        if item == SettingItem::Theme {
+            // the theme picked here replaces the one given on the command line
+            self.launch.theme = None;
+        }
+        self.save_settings();
+    }
+
+    fn handle_key_bindings_key(&mut self, key: Key) {
+        // "press a key to bind" prompt, esc cancels it
+        if let Some(action) = self.binding_capture.take() {
+            if key != Key::Esc {
+                match self.settings.keys.rebind(action, key) {
+                    Ok(()) => self.save_settings(),
+                    Err(why) => self.settings_message = Some(why),
                 }
             }
+            return;
         }
-    }
 
-    /// return true if game over
-    pub fn compute_next_frame(&mut self) -> bool {
-        if self.tetrominoe.now.elapsed() >= self.tetrominoe.still_time {
-            self.clear_tetrominoe();
-            self.clear_nt();
-            match self.is_collision() {
-                true => {
-                    if self.tetrominoe.vertices_pos.iter().any(|vec| vec.y == 1) {
-                        return true; // Game over
-                    }
+        // every action, then BACK
+        let n = Action::ALL.len() + 1;
+        match key {
+            Key::Up | Key::Char('w') | Key::Char('k') => {
+                self.bindings_selected = (self.bindings_selected + n - 1) % n
+            }
+            Key::Down | Key::Char('s') | Key::Char('j') => {
+                self.bindings_selected = (self.bindings_selected + 1) % n
+            }
+            Key::Backspace => {
+                self.settings.keys = KeyBindings::default();
+                self.save_settings();
+            }
+            Key::Esc | Key::Char('q') => self.leave_settings(self.bindings_return),
+            Key::Char('\n') | Key::Char(' ') => match Action::ALL.get(self.bindings_selected) {
+                Some(action) => {
+                    self.settings_message = None;
+                    self.binding_capture = Some(*action);
+                }
+                None => self.leave_settings(self.bindings_return),
+            },
+            _ => {}
+        }
+    }
 
-                    if self.log_new_border().is_ok() {
-                        self.draw_tetrominoe(self.tetrominoe.color); // persistent image
-                        self.check_row_clear();
-                    }
-                    self.pick_next_tetrominoe();
+    fn handle_game_over_key(&mut self, key: Key) -> bool {
+        match key {
+            Key::Up | Key::Char('w') | Key::Char('k') => self.game_over.list.move_cursor(-1),
+            Key::Down | Key::Char('s') | Key::Char('j') => self.game_over.list.move_cursor(1),
+            Key::Esc | Key::Char('q') => self.open_menu(),
+            Key::Char('\n') | Key::Char(' ') => match self.game_over.list.item() {
+                GameOverItem::NextPuzzle => {
+                    self.menu.next_puzzle();
+                    self.new_game(GameMode::Puzzle)
                 }
-                false => {
-                    self.tetrominoe.fall();
-                    self.tetrominoe.now = Instant::now();
+                GameOverItem::Restart => self.new_game(self.game.mode),
+                GameOverItem::Menu => self.open_menu(),
+                GameOverItem::SaveReplay => {
+                    self.game_over.messages = vec![match self.replay.save() {
+                        Ok(path) => format!("replay saved to {}", path.display()),
+                        Err(why) => format!("couldn't save replay: {why}"),
+                    }]
                 }
-            }
-            self.draw_tetrominoe(self.tetrominoe.color);
-            self.draw_nt();
+                GameOverItem::Quit => return true,
+            },
+            // straight back into the game, a failed puzzle in particular
+            key => match self.settings.keys.action(key) {
+                Some(Action::Restart) => self.new_game(self.game.mode),
+                Some(Action::Retry) => self.retry_game(),
+                _ => {}
+            },
         }
         false
     }
+
+    fn handle_game_key(&mut self, key: Key) {
+        let Some(action) = self.settings.keys.action(key) else {
+            return;
+        };
+        // only pausing, restarting and quitting while watching a replay or the bot
+        if action.is_gameplay() && !matches!(self.control, Control::Player) {
+            return;
+        }
+        // auto-repeat of held keys is handled by `update_held_inputs`
+        let new_press = match action {
+            Action::MoveLeft | Action::MoveRight => self.autoshift.press(action, self.game.tick),
+            Action::SoftDrop => self.soft_drop.press(action, self.game.tick),
+            _ => true,
+        };
+        if new_press {
+            if matches!(
+                action,
+                Action::MoveLeft
+                    | Action::MoveRight
+                    | Action::RotateCw
+                    | Action::RotateCcw
+                    | Action::Rotate180
+            ) {
+                self.game.finesse.inputs += 1;
+            }
+            self.perform(action)
+        }
+    }
+
+    /// slides the piece while a move key is held and keeps soft dropping while the soft drop key is
+    fn update_held_inputs(&mut self) {
+        let (das, arr) = self
+            .game
+            .rules
+            .autoshift
+            .unwrap_or((self.settings.das, self.settings.arr));
+        if let (Some(action), shifts) = self.autoshift.update(self.game.tick, das, arr) {
+            for _ in 0..shifts {
+                let before = self.game.tetrominoe.pos;
+                self.perform(action);
+                if self.game.tetrominoe.pos == before {
+                    break; // against the wall
+                }
+            }
+        }
+
+        let gravity = self.game.gravity();
+        let interval =
+            (gravity.ticks / (gravity.rows * self.settings.soft_drop_factor).max(1)).max(1);
+        if let (Some(action), drops) = self.soft_drop.update(self.game.tick, 0, interval) {
+            for _ in 0..drops {
+                self.perform(action)
+            }
+        }
+    }
+
+    /// plays the events of the replay that happened on this tick
+    fn play_replay_events(&mut self) {
+        loop {
+            let action = match &mut self.control {
+                Control::Replay { replay, next } => match replay.events.get(*next) {
+                    Some((tick, action)) if *tick <= self.game.tick => {
+                        *next += 1;
+                        *action
+                    }
+                    _ => return,
+                },
+                _ => return,
+            };
+            self.perform(action)
+        }
+    }
+
+    /// applies an action, recording it in the replay if it acts on the piece
+    fn perform(&mut self, action: Action) {
+        if action.is_gameplay() {
+            self.replay.record(self.game.tick, action);
+        }
+        self.apply_action(action)
+    }
+
+    fn apply_action(&mut self, action: Action) {
+        match action {
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::Pause => self.pause_game(),
+            Action::Restart => self.new_game(self.game.mode),
+            Action::Retry => self.retry_game(),
+            Action::Quit => self.open_menu(),
+            action => self.game.apply(action),
+        }
+    }
+
+    /// keeps the snapshots of the spawns of the placed pieces, in the modes with undo
+    fn update_history(&mut self) {
+        if !self.game.mode.has_undo() {
+            return;
+        }
+        if let Some(spawned) = &self.spawned {
+            // the piece locked, the new placement can't be redone over
+            if spawned.stats.pieces != self.game.stats.pieces {
+                self.undo.extend(self.spawned.take());
+                self.redo.clear();
+            }
+        }
+        if self.spawned.is_none() && self.game.piece_in_play() {
+            self.spawned = Some(self.game.clone());
+        }
+    }
+
+    /// back to the spawn of the last piece placed
+    fn undo(&mut self) {
+        if let Some(previous) = self.undo.pop() {
+            let current = self.spawned.take().unwrap_or_else(|| self.game.clone());
+            self.redo.push(current);
+            self.restore(previous);
+        }
+    }
+
+    /// forward to the spawn of the piece after the last undone placement
+    fn redo(&mut self) {
+        if let Some(next) = self.redo.pop() {
+            let current = self.spawned.take().unwrap_or_else(|| self.game.clone());
+            self.undo.push(current);
+            self.restore(next);
+        }
+    }
+
+    /// puts a snapshot of the game back, the clocks keep running
+    fn restore(&mut self, snapshot: GameState) {
+        let (tick, ticks) = (self.game.tick, self.game.stats.ticks);
+        self.game = snapshot.clone();
+        self.game.tick = tick;
+        self.game.stats.ticks = ticks;
+        self.game.callout = None;
+        self.spawned = Some(snapshot);
+        self.autoshift.reset();
+        self.soft_drop.reset();
+    }
 }
@@ -0,0 +1,354 @@
+use std::time::Duration;
+
+use super::{
+    board::{Board, TopOut},
+    rules::Rules,
+    stats::{Scoring, Stats, MASTER_MAX_LEVEL},
+    TICKS_PER_SECOND,
+};
+
+/// score needed for each grade of Master, as in TGM
+const MASTER_GRADES: [(usize, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1400, "6"),
+    (2000, "5"),
+    (3500, "4"),
+    (5500, "3"),
+    (8000, "2"),
+    (12000, "1"),
+    (16000, "S1"),
+    (22000, "S2"),
+    (30000, "S3"),
+    (40000, "S4"),
+    (52000, "S5"),
+    (66000, "S6"),
+    (82000, "S7"),
+    (100000, "S8"),
+    (120000, "S9"),
+];
+/// the GM grade takes reaching the last level with this score, in less than 13:30
+const GM_SCORE: usize = 126000;
+const GM_TICKS: usize = (13 * 60 + 30) * TICKS_PER_SECOND;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GameMode {
+    /// endless game, speed goes up with the level
+    Marathon,
+    /// clear the given number of lines as fast as possible
+    Sprint(usize),
+    /// score as much as possible in the given number of minutes
+    Ultra(u64),
+    /// clear the given number of garbage rows the board starts with as fast as possible
+    Dig(usize),
+    /// last as long as possible while garbage rows rise faster and faster
+    Survival,
+    /// climb to level 999 as gravity goes up to 20G and the delays get shorter, for a grade
+    Master,
+    /// no goal nor top out, pieces only fall when dropped and placements can be undone
+    Zen,
+    /// reach the goal of a puzzle from its board with its pieces
+    Puzzle,
+}
+
+/// how the results of a mode are ranked
+#[derive(Clone, Copy, PartialEq)]
+pub enum Ranking {
+    /// highest score first
+    Score,
+    /// fastest time first
+    Time,
+    /// longest time first, then most lines
+    Survival,
+}
+
+/// how a game ended
+#[derive(Clone, Copy, PartialEq)]
+pub enum Ending {
+    TopOut(TopOut),
+    /// the goal of the mode was reached, or its time is up
+    Finished,
+    /// a puzzle ran out of pieces before its goal was reached
+    Failed,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 13] = [
+        GameMode::Marathon,
+        GameMode::Sprint(20),
+        GameMode::Sprint(40),
+        GameMode::Sprint(100),
+        GameMode::Ultra(2),
+        GameMode::Ultra(3),
+        GameMode::Ultra(5),
+        GameMode::Dig(10),
+        GameMode::Dig(18),
+        GameMode::Survival,
+        GameMode::Master,
+        GameMode::Zen,
+        GameMode::Puzzle,
+    ];
+
+    /// also the key of the mode in the score and replay files, so it has no spaces
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Marathon => "MARATHON",
+            GameMode::Sprint(20) => "SPRINT20",
+            GameMode::Sprint(100) => "SPRINT100",
+            GameMode::Sprint(_) => "SPRINT40",
+            GameMode::Ultra(3) => "ULTRA3M",
+            GameMode::Ultra(5) => "ULTRA5M",
+            GameMode::Ultra(_) => "ULTRA2M",
+            GameMode::Dig(18) => "DIG18",
+            GameMode::Dig(_) => "DIG10",
+            GameMode::Survival => "SURVIVAL",
+            GameMode::Master => "MASTER",
+            GameMode::Zen => "ZEN",
+            GameMode::Puzzle => "PUZZLE",
+        }
+    }
+
+    /// case insensitive
+    pub fn from_name(name: &str) -> Option<Self> {
+        GameMode::ALL
+            .into_iter()
+            .find(|m| m.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn ranking(&self) -> Ranking {
+        match self {
+            GameMode::Marathon | GameMode::Ultra(_) | GameMode::Master | GameMode::Zen => {
+                Ranking::Score
+            }
+            GameMode::Sprint(_) | GameMode::Dig(_) | GameMode::Puzzle => Ranking::Time,
+            GameMode::Survival => Ranking::Survival,
+        }
+    }
+
+    /// rule set the mode is played with, `None` when it's up to the settings
+    pub fn rules(&self) -> Option<Rules> {
+        match self {
+            GameMode::Master => Some(Rules::Master),
+            // ultra scores are only comparable under the same scoring
+            GameMode::Ultra(_) => Some(Rules::Guideline),
+            // puzzles are made for SRS, hold and T-spins
+            GameMode::Puzzle => Some(Rules::Guideline),
+            _ => None,
+        }
+    }
+
+    /// level the game starts at with that scoring (not below its first level), Master always
+    /// starts from 0
+    pub fn start_level(&self, chosen: Option<usize>, scoring: Scoring) -> usize {
+        match self {
+            GameMode::Master => 0,
+            _ => chosen.map_or(scoring.first_level(), |level| {
+                level.max(scoring.first_level())
+            }),
+        }
+    }
+
+    /// grade of a Master game so far
+    pub fn grade(&self, stats: &Stats) -> Option<&'static str> {
+        if *self != GameMode::Master {
+            return None;
+        }
+        if stats.level >= MASTER_MAX_LEVEL && stats.score >= GM_SCORE && stats.ticks <= GM_TICKS {
+            return Some("GM");
+        }
+        MASTER_GRADES
+            .iter()
+            .rev()
+            .find(|(score, _)| stats.score >= *score)
+            .map(|(_, grade)| *grade)
+    }
+
+    /// number of lines ending the game, if any
+    pub fn line_goal(&self) -> Option<usize> {
+        match self {
+            GameMode::Sprint(lines) => Some(*lines),
+            _ => None,
+        }
+    }
+
+    /// number of garbage rows the game starts with, kept below the top of a `height` rows board
+    pub fn garbage_rows(&self, height: usize) -> usize {
+        match self {
+            GameMode::Dig(rows) => (*rows).min(height - 2),
+            _ => 0,
+        }
+    }
+
+    /// ticks before the next garbage row rises when `risen` rows already did, if rows rise
+    pub fn rise_interval(&self, risen: usize) -> Option<u64> {
+        // from 8s down to 1s, 0.2s faster every row
+        const FIRST: u64 = 8 * TICKS_PER_SECOND as u64;
+        const FASTEST: u64 = TICKS_PER_SECOND as u64;
+        const SPEED_UP: u64 = TICKS_PER_SECOND as u64 / 5;
+        match self {
+            GameMode::Survival => Some(FIRST.saturating_sub(risen as u64 * SPEED_UP).max(FASTEST)),
+            _ => None,
+        }
+    }
+
+    /// game time after which the game ends, if any. It's counted in ticks so replays end on the
+    /// same frame
+    pub fn time_limit(&self) -> Option<Duration> {
+        match self {
+            GameMode::Ultra(minutes) => Some(Duration::from_secs(minutes * 60)),
+            _ => None,
+        }
+    }
+
+    /// game time left, for the modes with a time limit
+    pub fn time_left(&self, stats: &Stats) -> Option<Duration> {
+        Some(self.time_limit()?.saturating_sub(stats.elapsed()))
+    }
+
+    /// whether the game is won, checked on every frame and after every lock (`clearing` being the
+    /// rows the piece fills)
+    pub fn is_finished(&self, stats: &Stats, board: &Board, clearing: &[usize]) -> bool {
+        match self {
+            // the goal of a puzzle is checked by the game, it depends on the last piece
+            GameMode::Marathon | GameMode::Survival | GameMode::Zen | GameMode::Puzzle => false,
+            GameMode::Sprint(lines) => stats.lines >= *lines,
+            GameMode::Ultra(_) => self.time_left(stats) == Some(Duration::ZERO),
+            GameMode::Dig(_) => board.garbage_left(clearing) == 0,
+            GameMode::Master => stats.level >= MASTER_MAX_LEVEL,
+        }
+    }
+
+    /// whether a stack reaching the top ends the game, Zen clears it instead
+    pub fn tops_out(&self) -> bool {
+        *self != GameMode::Zen
+    }
+
+    /// whether placements can be undone
+    pub fn has_undo(&self) -> bool {
+        *self == GameMode::Zen
+    }
+
+    /// whether a game that ended this way goes to the high scores: a mode with a goal only
+    /// ranks the games that reached it, and puzzles never are
+    pub fn is_ranked(&self, ending: Ending) -> bool {
+        match self {
+            GameMode::Marathon | GameMode::Survival | GameMode::Master => true,
+            GameMode::Puzzle => false,
+            _ => ending == Ending::Finished,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nanorand::WyRand;
+
+    use super::*;
+
+    #[test]
+    fn names_read_back() {
+        for mode in GameMode::ALL {
+            assert!(GameMode::from_name(mode.name()) == Some(mode));
+        }
+        assert!(GameMode::from_name("sprint40") == Some(GameMode::Sprint(40)));
+        assert!(GameMode::from_name("sprint").is_none());
+    }
+
+    #[test]
+    fn ultra_and_master_impose_their_rules() {
+        assert!(GameMode::Ultra(3).rules() == Some(Rules::Guideline));
+        assert!(GameMode::Master.rules() == Some(Rules::Master));
+        assert!(GameMode::Marathon.rules().is_none());
+    }
+
+    #[test]
+    fn sprints_only_rank_finished_games() {
+        let sprint = GameMode::Sprint(40);
+        let board = Board::new(10, 20);
+        let mut stats = Stats::new(1, Scoring::Guideline);
+        stats.lines = 39;
+        assert!(!sprint.is_finished(&stats, &board, &[]));
+        stats.lines = 41;
+        assert!(sprint.is_finished(&stats, &board, &[]));
+        assert!(!GameMode::Marathon.is_finished(&stats, &board, &[]));
+
+        assert!(sprint.is_ranked(Ending::Finished));
+        assert!(!sprint.is_ranked(Ending::TopOut(TopOut::Block)));
+        assert!(GameMode::Marathon.is_ranked(Ending::TopOut(TopOut::Lock)));
+    }
+
+    #[test]
+    fn ultras_end_when_the_time_is_up() {
+        let ultra = GameMode::Ultra(2);
+        let board = Board::new(10, 20);
+        let mut stats = Stats::new(1, Scoring::Guideline);
+        stats.ticks = 2 * 60 * TICKS_PER_SECOND - 30;
+        assert_eq!(ultra.time_left(&stats), Some(Duration::from_millis(500)));
+        assert!(!ultra.is_finished(&stats, &board, &[]));
+        stats.ticks += 30;
+        assert!(ultra.is_finished(&stats, &board, &[]));
+        assert_eq!(GameMode::Marathon.time_left(&stats), None);
+
+        assert!(ultra.is_ranked(Ending::Finished));
+        assert!(!ultra.is_ranked(Ending::TopOut(TopOut::Block)));
+    }
+
+    #[test]
+    fn digs_end_with_the_last_garbage_row() {
+        let dig = GameMode::Dig(10);
+        assert_eq!(dig.garbage_rows(20), 10);
+        // a short board keeps room to spawn
+        assert_eq!(GameMode::Dig(18).garbage_rows(12), 10);
+        assert_eq!(GameMode::Marathon.garbage_rows(20), 0);
+
+        let stats = Stats::new(1, Scoring::Guideline);
+        let mut board = Board::new(10, 20);
+        assert!(dig.is_finished(&stats, &board, &[]));
+        board.add_garbage(1, 0, &mut WyRand::new_seed(0));
+        assert!(!dig.is_finished(&stats, &board, &[]));
+        // finished on the lock filling the last row
+        let last = board.column(0).count() - 1;
+        assert!(dig.is_finished(&stats, &board, &[last]));
+    }
+
+    #[test]
+    fn survival_garbage_speeds_up() {
+        let second = TICKS_PER_SECOND as u64;
+        assert_eq!(GameMode::Survival.rise_interval(0), Some(8 * second));
+        assert_eq!(GameMode::Survival.rise_interval(5), Some(7 * second));
+        assert_eq!(GameMode::Survival.rise_interval(100), Some(second));
+        assert_eq!(GameMode::Dig(10).rise_interval(0), None);
+        assert!(GameMode::Survival.is_ranked(Ending::TopOut(TopOut::Garbage)));
+    }
+
+    #[test]
+    fn start_levels_follow_the_scoring() {
+        assert_eq!(GameMode::Master.start_level(Some(5), Scoring::Master), 0);
+        assert_eq!(
+            GameMode::Marathon.start_level(Some(5), Scoring::Guideline),
+            5
+        );
+        assert_eq!(GameMode::Marathon.start_level(None, Scoring::Nes), 0);
+        assert_eq!(
+            GameMode::Marathon.start_level(Some(0), Scoring::Guideline),
+            1
+        );
+    }
+
+    #[test]
+    fn master_grades_follow_the_score() {
+        let mut stats = Stats::new(0, Scoring::Master);
+        assert_eq!(GameMode::Master.grade(&stats), Some("9"));
+        stats.score = 16_500;
+        assert_eq!(GameMode::Master.grade(&stats), Some("S1"));
+        assert_eq!(GameMode::Marathon.grade(&stats), None);
+
+        (stats.score, stats.level) = (GM_SCORE, MASTER_MAX_LEVEL);
+        stats.ticks = GM_TICKS;
+        assert_eq!(GameMode::Master.grade(&stats), Some("GM"));
+        // too slow for the GM
+        stats.ticks += 1;
+        assert_eq!(GameMode::Master.grade(&stats), Some("S9"));
+    }
+}
@@ -0,0 +1,369 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    board::Board,
+    settings::{BOARD_HEIGHT_RANGE, BOARD_WIDTH_RANGE},
+    stats::Stats,
+    tspin::Spin,
+    types::TetrominoeType,
+};
+
+/// puzzles shipped with the game, in the order they're offered
+const BUNDLED: [&str; 5] = [
+    include_str!("../../puzzles/01-tetris.toml"),
+    include_str!("../../puzzles/02-perfect-clear.toml"),
+    include_str!("../../puzzles/03-t-spin-double.toml"),
+    include_str!("../../puzzles/04-t-spin-triple.toml"),
+    include_str!("../../puzzles/05-four-line-pc.toml"),
+];
+
+/// what a puzzle asks for
+#[derive(Clone, Copy, PartialEq)]
+pub enum Goal {
+    /// clear that many lines in total
+    Lines(usize),
+    /// leave the board empty after a line clear
+    PerfectClear,
+    /// clear 2 lines with a T-spin
+    TSpinDouble,
+    /// clear 3 lines with a T-spin
+    TSpinTriple,
+}
+
+impl Goal {
+    pub fn label(&self) -> String {
+        match self {
+            Goal::Lines(1) => "1 LINE".to_string(),
+            Goal::Lines(lines) => format!("{lines} LINES"),
+            Goal::PerfectClear => "PERFECT CLEAR".to_string(),
+            Goal::TSpinDouble => "T-SPIN DOUBLE".to_string(),
+            Goal::TSpinTriple => "T-SPIN TRIPLE".to_string(),
+        }
+    }
+
+    /// whether the piece that just locked reached the goal: `rows` are the rows it filled, not
+    /// removed from `board` yet, and `spin` how it got there
+    pub fn is_reached(
+        &self,
+        stats: &Stats,
+        board: &Board,
+        rows: &[usize],
+        spin: Option<Spin>,
+    ) -> bool {
+        match self {
+            Goal::Lines(lines) => stats.lines >= *lines,
+            Goal::PerfectClear => !rows.is_empty() && board.is_empty_but(rows),
+            Goal::TSpinDouble => spin == Some(Spin::Full) && rows.len() == 2,
+            Goal::TSpinTriple => spin == Some(Spin::Full) && rows.len() == 3,
+        }
+    }
+}
+
+/// a starting board, a fixed queue of pieces and a goal to reach with them
+#[derive(Clone, PartialEq)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    /// bottom rows of the board, top to bottom, `None` for empty cells
+    pub rows: Vec<Vec<Option<TetrominoeType>>>,
+    /// every piece the player gets, in order
+    pub queue: Vec<TetrominoeType>,
+    /// piece in the hold at the start
+    pub hold: Option<TetrominoeType>,
+}
+
+/// a puzzle as written in its file, boards are rows of `.` and piece letters
+#[derive(Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct PuzzleFile {
+    name: String,
+    /// `lines`, `perfect_clear`, `t_spin_double` or `t_spin_triple`
+    goal: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<usize>,
+    queue: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hold: Option<String>,
+    board: String,
+}
+
+impl Puzzle {
+    /// the puzzles shipped with the game
+    pub fn bundled() -> Vec<Puzzle> {
+        BUNDLED
+            .iter()
+            .map(|content| Puzzle::parse(content).expect("bundled puzzles are valid"))
+            .collect()
+    }
+
+    /// reads a puzzle file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|why| anyhow!("couldn't read puzzle {}: {why}", path.display()))?;
+        Self::parse(&content).map_err(|why| anyhow!("invalid puzzle {}:\n{why}", path.display()))
+    }
+
+    /// a puzzle from the content of its file, the error lists everything wrong with it
+    pub fn parse(content: &str) -> Result<Self> {
+        let file: PuzzleFile = toml::from_str(content)?;
+        let mut errors = vec![];
+
+        let goal = match (file.goal.as_str(), file.lines) {
+            ("lines", Some(lines)) if lines > 0 => Some(Goal::Lines(lines)),
+            ("lines", _) => {
+                errors.push("the `lines` goal needs `lines`, a number of lines".to_string());
+                None
+            }
+            ("perfect_clear", None) => Some(Goal::PerfectClear),
+            ("t_spin_double", None) => Some(Goal::TSpinDouble),
+            ("t_spin_triple", None) => Some(Goal::TSpinTriple),
+            ("perfect_clear" | "t_spin_double" | "t_spin_triple", Some(_)) => {
+                errors.push("`lines` only goes with the `lines` goal".to_string());
+                None
+            }
+            (goal, _) => {
+                errors.push(format!(
+                    "unknown goal `{goal}`, expected lines, perfect_clear, t_spin_double or \
+                     t_spin_triple"
+                ));
+                None
+            }
+        };
+
+        let mut pieces = |field: &str, letters: &str| {
+            letters
+                .chars()
+                .filter_map(|c| {
+                    let piece = TetrominoeType::from_letter(c);
+                    if piece.is_none() {
+                        errors.push(format!("unknown piece `{c}` in `{field}`"));
+                    }
+                    piece
+                })
+                .collect::<Vec<_>>()
+        };
+        let queue = pieces("queue", &file.queue);
+        let hold = match file.hold.as_deref().map(|hold| pieces("hold", hold)) {
+            None => None,
+            Some(hold) if hold.len() == 1 => Some(hold[0]),
+            Some(_) => {
+                errors.push("`hold` must be a single piece".to_string());
+                None
+            }
+        };
+        if queue.is_empty() {
+            errors.push("`queue` must have at least one piece".to_string());
+        }
+
+        let rows = file
+            .board
+            .lines()
+            .map(str::trim)
+            .filter(|row| !row.is_empty())
+            .enumerate()
+            .map(|(i, row)| {
+                let unknown = row
+                    .chars()
+                    .find(|c| *c != '.' && TetrominoeType::from_letter(*c).is_none());
+                if let Some(c) = unknown {
+                    errors.push(format!("unknown block `{c}` on row {} of `board`", i + 1));
+                }
+                row.chars()
+                    .map(TetrominoeType::from_letter)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let width = rows.first().map_or(0, |row| row.len());
+        if !BOARD_WIDTH_RANGE.contains(&width) {
+            errors.push(format!(
+                "`board` must be {} to {} blocks wide, got {width}",
+                BOARD_WIDTH_RANGE.start(),
+                BOARD_WIDTH_RANGE.end()
+            ));
+        }
+        if rows.iter().any(|row| row.len() != width) {
+            errors.push("the rows of `board` must all be as wide".to_string());
+        }
+        if rows.len() > Puzzle::max_rows() {
+            errors.push(format!(
+                "`board` must have at most {} rows, got {}",
+                Puzzle::max_rows(),
+                rows.len()
+            ));
+        }
+        if let Some(i) = rows.iter().position(|row| row.iter().all(Option::is_some)) {
+            errors.push(format!("row {} of `board` is full", i + 1));
+        }
+
+        match goal {
+            Some(goal) if errors.is_empty() => Ok(Puzzle {
+                name: file.name,
+                goal,
+                rows,
+                queue,
+                hold,
+            }),
+            _ => Err(anyhow!(
+                "{}",
+                errors
+                    .iter()
+                    .map(|e| format!("    - {e}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )),
+        }
+    }
+
+    /// the puzzle in the format of its file
+    pub fn to_toml(&self) -> String {
+        let letters = |pieces: &[TetrominoeType]| pieces.iter().map(|p| p.letter()).collect();
+        let board = self
+            .rows
+            .iter()
+            .map(|row| {
+                let row: String = row.iter().map(|b| b.map_or('.', |p| p.letter())).collect();
+                format!("{row}\n")
+            })
+            .collect();
+        let (goal, lines) = match self.goal {
+            Goal::Lines(lines) => ("lines", Some(lines)),
+            Goal::PerfectClear => ("perfect_clear", None),
+            Goal::TSpinDouble => ("t_spin_double", None),
+            Goal::TSpinTriple => ("t_spin_triple", None),
+        };
+        let file = PuzzleFile {
+            name: self.name.clone(),
+            goal: goal.to_string(),
+            lines,
+            queue: letters(&self.queue),
+            hold: self.hold.map(|p| p.letter().to_string()),
+            board,
+        };
+        toml::to_string(&file).unwrap_or_default()
+    }
+
+    /// pieces the player gets, the held one included
+    pub fn piece_count(&self) -> usize {
+        self.queue.len() + self.hold.is_some() as usize
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    /// most rows a board can start with, leaving 2 rows for the pieces to come in on the
+    /// highest boards
+    fn max_rows() -> usize {
+        BOARD_HEIGHT_RANGE.end() - 2
+    }
+
+    /// lowest board the puzzle can be played on
+    pub fn min_height(&self) -> usize {
+        (self.rows.len() + 2).max(*BOARD_HEIGHT_RANGE.start())
+    }
+
+    /// whether the puzzle can be played on a board of that size
+    pub fn fits(&self, width: usize, height: usize) -> bool {
+        width == self.width() && height >= self.min_height()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::stats::Scoring;
+
+    #[test]
+    fn bundled_puzzles_are_valid() {
+        let puzzles = Puzzle::bundled();
+        let goals = puzzles.iter().map(|p| p.goal.label()).collect::<Vec<_>>();
+        assert_eq!(
+            goals,
+            [
+                "4 LINES",
+                "PERFECT CLEAR",
+                "T-SPIN DOUBLE",
+                "T-SPIN TRIPLE",
+                "PERFECT CLEAR"
+            ]
+        );
+        for puzzle in &puzzles {
+            assert!(puzzle.fits(10, 20), "{} doesn't fit", puzzle.name);
+            assert!(Puzzle::parse(&puzzle.to_toml()).unwrap() == *puzzle);
+        }
+
+        let tsd = &puzzles[2];
+        assert_eq!(tsd.queue, [TetrominoeType::T]);
+        assert_eq!(tsd.rows.len(), 3);
+        assert_eq!(
+            tsd.rows[0][..3],
+            [
+                Some(TetrominoeType::Z),
+                Some(TetrominoeType::Z),
+                Some(TetrominoeType::L)
+            ]
+        );
+        assert_eq!(tsd.rows[0][3], None);
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        let errors = Puzzle::parse(
+            r#"
+name = "BROKEN"
+goal = "t_spin_quad"
+queue = "TX"
+hold = "IO"
+board = """
+..........
+..#.......
+IIII.
+"""
+"#,
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            errors,
+            [
+                "    - unknown goal `t_spin_quad`, expected lines, perfect_clear, t_spin_double or t_spin_triple",
+                "    - unknown piece `X` in `queue`",
+                "    - `hold` must be a single piece",
+                "    - unknown block `#` on row 2 of `board`",
+                "    - the rows of `board` must all be as wide",
+            ]
+            .join("\n")
+        );
+
+        let errors =
+            Puzzle::parse("name = \"\"\ngoal = \"lines\"\nqueue = \"\"\nboard = \"IIIIIIIIII\"")
+                .err()
+                .unwrap()
+                .to_string();
+        assert_eq!(
+            errors,
+            [
+                "    - the `lines` goal needs `lines`, a number of lines",
+                "    - `queue` must have at least one piece",
+                "    - row 1 of `board` is full",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn t_spin_goals_need_a_full_t_spin() {
+        let stats = Stats::new(1, Scoring::Guideline);
+        let board = Board::new(10, 20);
+        let tsd = Goal::TSpinDouble;
+        assert!(tsd.is_reached(&stats, &board, &[38, 39], Some(Spin::Full)));
+        assert!(!tsd.is_reached(&stats, &board, &[38, 39], Some(Spin::Mini)));
+        assert!(!tsd.is_reached(&stats, &board, &[38, 39], None));
+        assert!(!tsd.is_reached(&stats, &board, &[39], Some(Spin::Full)));
+        assert!(!Goal::TSpinTriple.is_reached(&stats, &board, &[38, 39], Some(Spin::Full)));
+    }
+}
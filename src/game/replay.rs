@@ -0,0 +1,295 @@
+use std::{
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+
+use crate::utils::{data_dir, unix_time};
+
+use super::{
+    input::Action,
+    mode::GameMode,
+    puzzle::Puzzle,
+    rules::{RuleSet, Rules},
+    settings::{BOARD_HEIGHT_RANGE, BOARD_WIDTH_RANGE},
+};
+
+const HEADER: &str = "corrodis-replay 3";
+
+/// everything needed to replay a game: its seed and the actions of the player with the tick they happened on
+#[derive(Clone)]
+pub struct Replay {
+    pub mode: GameMode,
+    pub seed: u64,
+    /// starting level
+    pub level: usize,
+    /// board size in blocks, the game only plays the same on a board of the same size
+    pub width: usize,
+    pub height: usize,
+    pub rules: RuleSet,
+    /// the rule set `rules` come from, the one the scores of the game are kept under
+    pub preset: Rules,
+    /// chance (percent) that the hole of a garbage row isn't under the one of the row above
+    pub messiness: usize,
+    /// board, pieces and goal of a Puzzle game
+    pub puzzle: Option<Puzzle>,
+    pub events: Vec<(u64, Action)>,
+}
+
+impl Replay {
+    pub fn new(mode: GameMode, seed: u64, level: usize, width: usize, height: usize) -> Self {
+        Self {
+            mode,
+            seed,
+            level,
+            width,
+            height,
+            rules: RuleSet::default(),
+            preset: Rules::Guideline,
+            messiness: 0,
+            puzzle: None,
+            events: vec![],
+        }
+    }
+
+    /// reads a replay written by `save`
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|why| anyhow!("couldn't read replay {}: {why}", path.display()))?;
+        Self::parse(&content).map_err(|why| anyhow!("invalid replay {}: {why}", path.display()))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut lines = content.lines().enumerate();
+        if lines.next().map(|(_, l)| l) != Some(HEADER) {
+            return Err(anyhow!("not a replay file (expected `{HEADER}` first)"));
+        }
+
+        let mut replay = Replay::new(GameMode::Marathon, 0, 1, 0, 0);
+        let (mut mode, mut seed, mut board) = (None, None, None);
+        // the rule set as TOML, one line per `rules` line
+        let mut rules = String::new();
+        // the puzzle file, one line per `puzzle` line
+        let mut puzzle = String::new();
+        for (i, line) in lines {
+            let bad_line = || anyhow!("line {}: `{line}`", i + 1);
+            let (key, value) = line.split_once(' ').ok_or_else(bad_line)?;
+            match key {
+                "mode" => mode = Some(GameMode::from_name(value).ok_or_else(bad_line)?),
+                "seed" => seed = Some(value.parse().map_err(|_| bad_line())?),
+                "level" => replay.level = value.parse().map_err(|_| bad_line())?,
+                "rules" => rules += &format!("{value}\n"),
+                "preset" => replay.preset = Rules::from_name(value).ok_or_else(bad_line)?,
+                "puzzle" => puzzle += &format!("{value}\n"),
+                "messiness" => replay.messiness = value.parse().map_err(|_| bad_line())?,
+                "board" => {
+                    let (w, h) = value.split_once(' ').ok_or_else(bad_line)?;
+                    let parse = |n: &str, range: RangeInclusive<usize>| {
+                        n.parse::<usize>()
+                            .ok()
+                            .filter(|n| range.contains(n))
+                            .ok_or_else(bad_line)
+                    };
+                    board = Some((parse(w, BOARD_WIDTH_RANGE)?, parse(h, BOARD_HEIGHT_RANGE)?))
+                }
+                tick => {
+                    let tick = tick.parse().map_err(|_| bad_line())?;
+                    let action = Action::from_name(value).ok_or_else(bad_line)?;
+                    replay.events.push((tick, action))
+                }
+            }
+        }
+
+        if rules.is_empty() {
+            return Err(anyhow!("missing rules"));
+        }
+        replay.rules = toml::from_str(&rules).map_err(|why| anyhow!("invalid rules: {why}"))?;
+        if !puzzle.is_empty() {
+            replay.puzzle =
+                Some(Puzzle::parse(&puzzle).map_err(|why| anyhow!("invalid puzzle:\n{why}"))?);
+        }
+        replay.mode = mode.ok_or(anyhow!("missing mode"))?;
+        if replay.mode == GameMode::Puzzle && replay.puzzle.is_none() {
+            return Err(anyhow!("missing puzzle"));
+        }
+        replay.seed = seed.ok_or(anyhow!("missing seed"))?;
+        (replay.width, replay.height) = board.ok_or(anyhow!("missing board size"))?;
+        if let Some(puzzle) = &replay.puzzle {
+            if !puzzle.fits(replay.width, replay.height) {
+                return Err(anyhow!("the puzzle doesn't fit the board"));
+            }
+        }
+        Ok(replay)
+    }
+
+    pub fn record(&mut self, tick: u64, action: Action) {
+        self.events.push((tick, action))
+    }
+
+    /// directory `save` writes the replays to
+    fn dir() -> Result<PathBuf> {
+        Ok(data_dir()
+            .ok_or(anyhow!("couldn't find the data directory"))?
+            .join("replays"))
+    }
+
+    /// replay files of the replays directory, newest first
+    pub fn list() -> Result<Vec<PathBuf>> {
+        let dir = Self::dir()?;
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut files = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|e| e == "replay"))
+            .map(|path| (fs::metadata(&path).and_then(|m| m.modified()).ok(), path))
+            .collect::<Vec<_>>();
+        files.sort_by(|a, b| b.cmp(a));
+        Ok(files.into_iter().map(|(_, path)| path).collect())
+    }
+
+    /// writes the replay in the replays directory, returns the path of the file
+    pub fn save(&self) -> Result<PathBuf> {
+        let dir = Self::dir()?;
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join(format!(
+            "{}-{}.replay",
+            self.mode.name().to_lowercase(),
+            unix_time()
+        ));
+        self.save_to(&path)?;
+        Ok(path)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Replay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        writeln!(f, "mode {}", self.mode.name())?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "level {}", self.level)?;
+        writeln!(f, "board {} {}", self.width, self.height)?;
+        writeln!(f, "preset {}", self.preset.name())?;
+        let rules = toml::to_string(&self.rules).map_err(|_| std::fmt::Error)?;
+        for line in rules.lines() {
+            writeln!(f, "rules {line}")?;
+        }
+        if let Some(puzzle) = &self.puzzle {
+            for line in puzzle.to_toml().lines() {
+                writeln!(f, "puzzle {line}")?;
+            }
+        }
+        writeln!(f, "messiness {}", self.messiness)?;
+        for (tick, action) in &self.events {
+            writeln!(f, "{tick} {}", action.name())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{
+        kicks::{self, Kicks180},
+        settings::Settings,
+    };
+
+    #[test]
+    fn replays_list_the_actions_with_their_tick() {
+        let mut replay = Replay::new(GameMode::Marathon, 42, 1, 10, 20);
+        replay.record(12, Action::MoveLeft);
+        replay.record(12, Action::RotateCw);
+        let written = replay.to_string();
+        assert!(written.starts_with(&format!(
+            "{HEADER}\nmode MARATHON\nseed 42\nlevel 1\nboard 10 20\npreset guideline\nrules "
+        )));
+        assert!(written.ends_with("\nmessiness 0\n12 move_left\n12 rotate_cw\n"));
+    }
+
+    #[test]
+    fn saved_replays_read_back() {
+        let mut replay = Replay::new(GameMode::Marathon, 7, 3, 6, 14);
+        replay.rules = Rules::Classic.rule_set(&Settings::default());
+        replay.preset = Rules::Classic;
+        replay.messiness = 40;
+        replay.record(0, Action::HardDrop);
+        replay.record(95, Action::Hold);
+        let written = replay.to_string();
+        let read = Replay::parse(&written).unwrap();
+        assert_eq!(read.to_string(), written);
+        assert!(read.mode == GameMode::Marathon);
+        assert_eq!((read.seed, read.level), (7, 3));
+        assert_eq!((read.width, read.height), (6, 14));
+        assert!(read.rules == replay.rules && read.preset == Rules::Classic);
+        assert_eq!(read.messiness, 40);
+        assert_eq!(read.events, replay.events);
+
+        // the whole rule set is written, 180° kicks and IRS included
+        let mut custom = RuleSet {
+            irs: true,
+            line_clear_delay: 30,
+            kicks_180: Kicks180::None.table(&Default::default()),
+            ..RuleSet::default()
+        };
+        custom.kicks_180[2] = kicks::from_y_up(&[(0, 0), (1, 2)]);
+        replay.rules = custom.clone();
+        assert!(Replay::parse(&replay.to_string()).unwrap().rules == custom);
+
+        assert_eq!(
+            Replay::parse(&format!("{HEADER}\nmode MARATHON\n"))
+                .err()
+                .unwrap()
+                .to_string(),
+            "missing rules"
+        );
+    }
+
+    #[test]
+    fn puzzle_replays_keep_their_puzzle() {
+        let puzzle = Puzzle::bundled().remove(2);
+        let mut replay = Replay::new(GameMode::Puzzle, 1, 1, puzzle.width(), 20);
+        replay.puzzle = Some(puzzle.clone());
+
+        let read = Replay::parse(&replay.to_string()).unwrap();
+        assert!(read.puzzle == Some(puzzle));
+
+        // without it the game can't be played again
+        let without = replay
+            .to_string()
+            .lines()
+            .filter(|line| !line.starts_with("puzzle "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            Replay::parse(&without).err().unwrap().to_string(),
+            "missing puzzle"
+        );
+    }
+
+    #[test]
+    fn only_this_version_is_read() {
+        let replay = Replay::new(GameMode::Marathon, 1, 1, 10, 20).to_string();
+        let older = replay.replacen(HEADER, "corrodis-replay 2", 1);
+        assert!(Replay::parse(&older).is_err());
+
+        let without_seed = replay
+            .lines()
+            .filter(|line| !line.starts_with("seed "))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            Replay::parse(&without_seed).err().unwrap().to_string(),
+            "missing seed"
+        );
+    }
+}
@@ -0,0 +1,545 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Result};
+use nanorand::{Rng, WyRand};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    kicks::{self, KickTable, Kicks180},
+    settings::{Settings, ARR_RANGE, DAS_RANGE, LINE_CLEAR_DELAY_RANGE, NEXT_COUNT_RANGE},
+    stats::Scoring,
+    types::TetrominoeType,
+    LOCK_DELAY_TICKS, MAX_LOCK_RESETS,
+};
+
+/// gravity of the Guideline rules from a level on, `rows` rows every `ticks` ticks: the
+/// guideline's (0.8 - (level - 1) * 0.007)^(level - 1) seconds per row, rounded, up to 20G
+const GUIDELINE_GRAVITY: [(usize, usize, usize); 19] = [
+    (0, 1, 60),
+    (2, 1, 48),
+    (3, 1, 37),
+    (4, 1, 28),
+    (5, 1, 21),
+    (6, 1, 16),
+    (7, 1, 11),
+    (8, 1, 8),
+    (9, 1, 5),
+    (10, 1, 4),
+    (11, 2, 5),
+    (12, 2, 3),
+    (13, 1, 1),
+    (14, 7, 4),
+    (15, 3, 1),
+    (16, 5, 1),
+    (17, 8, 1),
+    (18, 14, 1),
+    (19, 20, 1),
+];
+/// gravity of the Classic rules from a level on, in ticks per row (NES, NTSC)
+const NES_GRAVITY: [(usize, usize); 15] = [
+    (0, 48),
+    (1, 43),
+    (2, 38),
+    (3, 33),
+    (4, 28),
+    (5, 23),
+    (6, 18),
+    (7, 13),
+    (8, 8),
+    (9, 6),
+    (10, 5),
+    (13, 4),
+    (16, 3),
+    (19, 2),
+    (29, 1),
+];
+/// gravity of the Master rules from a level on, in 1/256 rows per tick as in TGM
+const MASTER_GRAVITY: [(usize, usize); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 20 * 256),
+];
+/// entry delay and lock delay of the Master rules from a level on, shorter every section of 100
+/// levels past 500. Only an approximation of how the later TGM games speed up, not their exact
+/// values (the first TGM keeps both at 30 frames)
+const MASTER_ARE: [(usize, usize); 5] = [(0, 25), (600, 20), (700, 16), (800, 12), (900, 8)];
+const MASTER_LOCK_DELAY: [(usize, usize); 5] =
+    [(0, 30), (600, 26), (700, 22), (800, 18), (900, 15)];
+
+/// a speed of `rows` rows every `ticks` ticks
+#[derive(Clone, Copy, PartialEq)]
+pub struct Gravity {
+    pub rows: usize,
+    pub ticks: usize,
+}
+
+impl Gravity {
+    pub const fn new(rows: usize, ticks: usize) -> Self {
+        Self { rows, ticks }
+    }
+
+    /// 20 rows per tick or more: pieces fall to the floor as soon as they can
+    pub fn is_20g(&self) -> bool {
+        self.rows >= 20 * self.ticks
+    }
+}
+
+/// how pieces turn
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    /// Super Rotation System: every piece turns inside its bounding square
+    Srs,
+    /// right-handed Nintendo rotation: the T, J and L pieces spawn pointing down and turn
+    /// around their center block, the I, S and Z pieces only have two orientations
+    Nintendo,
+}
+
+/// how the next pieces are picked
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Randomizer {
+    /// any piece with the same chance
+    Random,
+    /// NES: a roll giving the previous piece (or an invalid one) is rerolled once
+    Nes,
+}
+
+impl Randomizer {
+    /// the piece coming after `previous`
+    pub fn next(&self, rng: &mut WyRand, previous: Option<TetrominoeType>) -> TetrominoeType {
+        match self {
+            Randomizer::Random => TetrominoeType::random(rng),
+            Randomizer::Nes => {
+                let roll = rng.generate_range(0_u8..=7);
+                if roll == 7 || previous == Some(roll.into()) {
+                    TetrominoeType::random(rng)
+                } else {
+                    roll.into()
+                }
+            }
+        }
+    }
+}
+
+/// what restarts the lock delay of a piece on the ground
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockReset {
+    /// reaching a new lowest row, and any move or turn up to `RuleSet::max_lock_resets` times
+    Move,
+    /// only reaching a new lowest row
+    Step,
+    /// nothing, the delay keeps running
+    None,
+}
+
+/// the mechanics of a game, as opposed to the goal of its mode. The values given by level are
+/// `[level, value]` pairs, lowest level first, each one used from its level on
+#[derive(Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleSet {
+    pub rotation: Rotation,
+    /// whether turns blocked in place try the SRS kicks (and the `kicks_180` ones)
+    pub kicks: bool,
+    /// whether pieces can be turned by 180° at once
+    pub rotate_180: bool,
+    /// whether a turn or a hold pressed during the entry delay applies to the spawning piece
+    /// (IRS/IHS)
+    pub irs: bool,
+    pub randomizer: Randomizer,
+    /// most next pieces shown, whatever the settings say
+    pub previews: usize,
+    pub hold: bool,
+    pub ghost: bool,
+    /// DAS and ARR forced by the rules, the ones of the settings otherwise
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub autoshift: Option<(usize, usize)>,
+    /// ticks a piece can stay on the ground before locking, by level
+    pub lock_delay: Vec<(usize, usize)>,
+    pub lock_reset: LockReset,
+    /// times moving restarts the lock delay on the same row with `LockReset::Move`
+    pub max_lock_resets: usize,
+    /// entry delay in ticks, by level
+    pub are: Vec<(usize, usize)>,
+    /// ticks the cleared rows stay on the board before the stack collapses
+    pub line_clear_delay: usize,
+    /// `[level, rows, ticks]`: from that level on, pieces fall `rows` rows every `ticks` ticks
+    pub gravity: Vec<(usize, usize, usize)>,
+    pub scoring: Scoring,
+    /// whether T-spins are detected, scored and called out
+    pub t_spins: bool,
+    /// also top out when a piece locks partly above the visible field, not only fully
+    pub partial_lock_out: bool,
+    /// kicks of the 180° turns, `[kicks_180]` with `"0>2" = [[x, y]...]` rows as in the settings
+    #[serde(with = "kicks::y_up")]
+    pub kicks_180: KickTable,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Rules::Guideline.rule_set(&Settings::default())
+    }
+}
+
+/// the value of a by level table at `level`
+fn at_level(table: &[(usize, usize)], level: usize) -> usize {
+    table
+        .iter()
+        .rev()
+        .find(|(from, _)| level >= *from)
+        .map_or(0, |(_, value)| *value)
+}
+
+impl RuleSet {
+    /// the guideline rules, with the delays, top out, IRS and 180° kicks of the settings
+    pub fn guideline(settings: &Settings) -> Self {
+        RuleSet {
+            rotation: Rotation::Srs,
+            kicks: true,
+            rotate_180: true,
+            irs: settings.irs,
+            randomizer: Randomizer::Random,
+            previews: *NEXT_COUNT_RANGE.end(),
+            hold: true,
+            ghost: true,
+            autoshift: None,
+            lock_delay: vec![(0, LOCK_DELAY_TICKS)],
+            lock_reset: LockReset::Move,
+            max_lock_resets: MAX_LOCK_RESETS,
+            are: vec![(0, settings.are)],
+            line_clear_delay: settings.line_clear_delay,
+            gravity: GUIDELINE_GRAVITY.to_vec(),
+            scoring: Scoring::Guideline,
+            t_spins: true,
+            partial_lock_out: settings.partial_lock_out,
+            kicks_180: settings.kicks_180.table(&settings.custom_kicks_180),
+        }
+    }
+
+    pub fn gravity(&self, level: usize) -> Gravity {
+        self.gravity
+            .iter()
+            .rev()
+            .find(|(from, _, _)| level >= *from)
+            .map_or(Gravity::new(0, 1), |(_, rows, ticks)| {
+                Gravity::new(*rows, *ticks)
+            })
+    }
+
+    pub fn lock_delay(&self, level: usize) -> usize {
+        at_level(&self.lock_delay, level)
+    }
+
+    pub fn are(&self, level: usize) -> usize {
+        at_level(&self.are, level)
+    }
+
+    /// reads a rule set file, rules left out are the guideline ones
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|why| anyhow!("couldn't read rules file {}: {why}", path.display()))?;
+        let rules: RuleSet = toml::from_str(&content)
+            .map_err(|why| anyhow!("invalid rules file {}:\n{why}", path.display()))?;
+        match rules.errors() {
+            errors if errors.is_empty() => Ok(rules),
+            errors => Err(anyhow!(
+                "invalid rules file {}:\n{}",
+                path.display(),
+                errors
+                    .iter()
+                    .map(|e| format!("    - {e}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )),
+        }
+    }
+
+    /// what's wrong with rules read from a file
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+        if self.previews > *NEXT_COUNT_RANGE.end() {
+            errors.push(format!(
+                "`previews` must be at most {}, got {}",
+                NEXT_COUNT_RANGE.end(),
+                self.previews
+            ));
+        }
+        if let Some((das, arr)) = self.autoshift {
+            if !DAS_RANGE.contains(&das) || !ARR_RANGE.contains(&arr) {
+                errors.push(format!(
+                    "`autoshift` must be [das, arr] with das in {DAS_RANGE:?} and arr in \
+                     {ARR_RANGE:?}, got [{das}, {arr}]"
+                ));
+            }
+        }
+        if !LINE_CLEAR_DELAY_RANGE.contains(&self.line_clear_delay) {
+            errors.push(format!(
+                "`line_clear_delay` must be between {} and {}, got {}",
+                LINE_CLEAR_DELAY_RANGE.start(),
+                LINE_CLEAR_DELAY_RANGE.end(),
+                self.line_clear_delay
+            ));
+        }
+        let tables = [
+            (
+                "lock_delay",
+                self.lock_delay.first().map(|(level, _)| *level),
+            ),
+            ("are", self.are.first().map(|(level, _)| *level)),
+            ("gravity", self.gravity.first().map(|(level, _, _)| *level)),
+        ];
+        for (name, first) in tables {
+            if first != Some(0) {
+                errors.push(format!("`{name}` must start at level 0"));
+            }
+        }
+        if self.gravity.iter().any(|(_, _, ticks)| *ticks == 0) {
+            errors.push("`gravity` can't fall every 0 ticks".to_string());
+        }
+        errors
+    }
+}
+
+/// the rule sets to choose from
+#[derive(Clone, Copy, PartialEq)]
+pub enum Rules {
+    /// modern rules, tuned by the settings (entry and line clear delays, top out)
+    Guideline,
+    /// NES Tetris
+    Classic,
+    /// TGM, up to 20G
+    Master,
+    /// `Settings::custom_rules`, read from a file
+    Custom,
+}
+
+impl Rules {
+    pub const ALL: [Rules; 4] = [
+        Rules::Guideline,
+        Rules::Classic,
+        Rules::Master,
+        Rules::Custom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rules::Guideline => "guideline",
+            Rules::Classic => "classic",
+            Rules::Master => "master",
+            Rules::Custom => "custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Rules::ALL.into_iter().find(|r| r.name() == name)
+    }
+
+    pub fn rule_set(&self, settings: &Settings) -> RuleSet {
+        match self {
+            Rules::Guideline => RuleSet::guideline(settings),
+            Rules::Classic => RuleSet {
+                rotation: Rotation::Nintendo,
+                kicks: false,
+                rotate_180: false,
+                irs: false,
+                randomizer: Randomizer::Nes,
+                previews: 1,
+                hold: false,
+                ghost: false,
+                autoshift: Some((16, 6)),
+                // a piece locks when it can't fall any further, on its next fall
+                lock_delay: NES_GRAVITY.to_vec(),
+                lock_reset: LockReset::None,
+                max_lock_resets: 0,
+                are: vec![(0, 10)],
+                line_clear_delay: 18,
+                gravity: NES_GRAVITY
+                    .iter()
+                    .map(|(level, ticks)| (*level, 1, *ticks))
+                    .collect(),
+                scoring: Scoring::Nes,
+                t_spins: false,
+                partial_lock_out: false,
+                kicks_180: Kicks180::None.table(&Default::default()),
+            },
+            Rules::Master => RuleSet {
+                rotation: Rotation::Srs,
+                kicks: true,
+                rotate_180: true,
+                irs: true,
+                randomizer: Randomizer::Random,
+                previews: 1,
+                hold: false,
+                ghost: true,
+                autoshift: None,
+                lock_delay: MASTER_LOCK_DELAY.to_vec(),
+                lock_reset: LockReset::Step,
+                max_lock_resets: 0,
+                are: MASTER_ARE.to_vec(),
+                line_clear_delay: 40,
+                gravity: MASTER_GRAVITY
+                    .iter()
+                    .map(|(level, rows)| (*level, *rows, 256))
+                    .collect(),
+                scoring: Scoring::Master,
+                t_spins: true,
+                partial_lock_out: false,
+                kicks_180: Kicks180::SrsPlus.table(&Default::default()),
+            },
+            // the guideline rules until a rules file is read
+            Rules::Custom => settings
+                .custom_rules
+                .clone()
+                .unwrap_or_else(|| RuleSet::guideline(settings)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_read_back() {
+        for rules in Rules::ALL {
+            assert!(Rules::from_name(rules.name()) == Some(rules));
+        }
+        assert!(Rules::from_name("nes").is_none());
+    }
+
+    #[test]
+    fn guideline_gravity_speeds_up_to_20g() {
+        let guideline = RuleSet::default();
+        assert!(guideline.gravity(1) == Gravity::new(1, 60));
+        assert!(guideline.gravity(10) == Gravity::new(1, 4));
+        assert!(!guideline.gravity(18).is_20g());
+        assert!(guideline.gravity(19).is_20g());
+        // never slower from one level to the next
+        let speed = |level| {
+            let gravity = guideline.gravity(level);
+            gravity.rows as f64 / gravity.ticks as f64
+        };
+        assert!((1..30).all(|level| speed(level + 1) >= speed(level)));
+    }
+
+    #[test]
+    fn classic_gravity_follows_the_nes_table() {
+        let classic = Rules::Classic.rule_set(&Settings::default());
+        let ticks = |level| classic.gravity(level).ticks;
+        assert_eq!((ticks(0), ticks(8), ticks(9)), (48, 8, 6));
+        // same speed from 10 to 12
+        assert_eq!((ticks(10), ticks(12), ticks(13)), (5, 5, 4));
+        assert_eq!((ticks(29), ticks(200)), (1, 1));
+    }
+
+    #[test]
+    fn master_speeds_up_by_level() {
+        let master = Rules::Master.rule_set(&Settings::default());
+        let gravity = |level| {
+            let gravity = master.gravity(level);
+            (gravity.rows, gravity.ticks)
+        };
+        assert_eq!(gravity(0), (4, 256));
+        assert_eq!(gravity(199), (144, 256));
+        // gravity drops back at 200
+        assert_eq!(gravity(200), (4, 256));
+        assert!(master.gravity(998).is_20g());
+        assert_eq!((master.are(650), master.lock_delay(650)), (20, 26));
+        assert_eq!((master.are(0), master.lock_delay(0)), (25, 30));
+    }
+
+    #[test]
+    fn only_the_guideline_rules_follow_the_settings() {
+        let settings = Settings {
+            irs: true,
+            are: 12,
+            kicks_180: Kicks180::None,
+            ..Settings::default()
+        };
+        let guideline = Rules::Guideline.rule_set(&settings);
+        assert!(guideline.irs);
+        assert_eq!(guideline.are(5), 12);
+        assert_eq!(
+            guideline.kicks_180,
+            Kicks180::None.table(&Default::default())
+        );
+        assert!(!Rules::Classic.rule_set(&settings).irs);
+        assert!(Rules::Master.rule_set(&Settings::default()).irs);
+        // custom rules without a rules file
+        assert!(Rules::Custom.rule_set(&settings) == guideline);
+    }
+
+    #[test]
+    fn rules_files_fill_in_the_guideline_rules() {
+        let rules: RuleSet = toml::from_str(
+            "hold = false\ngravity = [[0, 1, 10], [5, 1, 2]]\n[kicks_180]\n\"0>2\" = [[0, 1]]\n",
+        )
+        .unwrap();
+        assert!(!rules.hold && rules.ghost);
+        assert!(rules.gravity(7) == Gravity::new(1, 2));
+        assert_eq!(rules.kicks_180[0], kicks::from_y_up(&[(0, 1)]));
+        // turns left out only turn in place
+        assert_eq!(rules.kicks_180[1], kicks::from_y_up(&[(0, 0)]));
+        assert!(rules.errors().is_empty());
+        // written and read back whole, as in the replays
+        let written = toml::to_string(&rules).unwrap();
+        assert!(toml::from_str::<RuleSet>(&written).unwrap() == rules);
+
+        assert!(toml::from_str::<RuleSet>("[kicks_180]\n\"0>1\" = [[0, 1]]\n").is_err());
+        assert!(toml::from_str::<RuleSet>("spin = true\n").is_err());
+        let bad = RuleSet {
+            previews: 9,
+            are: vec![(3, 0)],
+            ..RuleSet::default()
+        };
+        assert_eq!(
+            bad.errors(),
+            [
+                "`previews` must be at most 4, got 9",
+                "`are` must start at level 0"
+            ]
+        );
+    }
+
+    #[test]
+    fn nes_randomizer_rerolls_repeats_once() {
+        let mut rng = WyRand::new_seed(7);
+        let pieces = (0..1000)
+            .scan(None, |previous, _| {
+                *previous = Some(Randomizer::Nes.next(&mut rng, *previous));
+                *previous
+            })
+            .collect::<Vec<_>>();
+        let repeats = pieces.windows(2).filter(|w| w[0] == w[1]).count();
+        // 1 in 7 without the reroll, 1 in 28 with it
+        assert!(repeats < 1000 / 20, "{repeats} repeats");
+    }
+}
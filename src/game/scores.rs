@@ -0,0 +1,297 @@
+use std::{cmp::Ordering, fs, path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, Result};
+
+use crate::utils::{data_dir, fmt_duration, fmt_duration_ms};
+
+use super::{
+    mode::{GameMode, Ranking},
+    rules::Rules,
+};
+
+/// number of scores kept per mode and rules
+const MAX_SCORES: usize = 10;
+
+#[derive(Clone)]
+pub struct ScoreEntry {
+    pub mode: String,
+    /// name of the rules the game was played with
+    pub rules: String,
+    pub score: usize,
+    pub lines: usize,
+    pub level: usize,
+    pub time: Duration,
+    /// unix timestamp of the end of the game
+    pub date: u64,
+    /// game time every `SPLIT_LINES` lines
+    pub splits: Vec<Duration>,
+}
+
+impl ScoreEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let mut next = || fields.next();
+        Some(Self {
+            mode: next()?.to_string(),
+            rules: next()?.to_string(),
+            score: next()?.parse().ok()?,
+            lines: next()?.parse().ok()?,
+            level: next()?.parse().ok()?,
+            time: Duration::from_millis(next()?.parse().ok()?),
+            date: next()?.parse().ok()?,
+            splits: match next()? {
+                "-" => vec![],
+                splits => splits
+                    .split(',')
+                    .map(|ms| Some(Duration::from_millis(ms.parse().ok()?)))
+                    .collect::<Option<_>>()?,
+            },
+        })
+    }
+
+    /// orders the entries of a mode best first
+    fn compare(ranking: Ranking, a: &ScoreEntry, b: &ScoreEntry) -> Ordering {
+        match ranking {
+            Ranking::Score => b.score.cmp(&a.score),
+            Ranking::Time => a.time.cmp(&b.time),
+            Ranking::Survival => b.time.cmp(&a.time).then(b.lines.cmp(&a.lines)),
+        }
+    }
+
+    /// whether both entries are ranked against each other: same mode, same rules
+    fn same_table(&self, other: &ScoreEntry) -> bool {
+        self.mode == other.mode && self.rules == other.rules
+    }
+
+    fn ranking(&self) -> Ranking {
+        GameMode::from_name(&self.mode).map_or(Ranking::Score, |m| m.ranking())
+    }
+
+    /// header of the score tables
+    pub fn table_header() -> String {
+        format!(
+            "{:<3} {:>9} {:>6} {:>5} {:>10}",
+            "#", "SCORE", "LINES", "LEVEL", "TIME"
+        )
+    }
+
+    /// the entry as a row of a score table, `rank` starting at 1
+    pub fn table_row(&self, rank: usize) -> String {
+        format!(
+            "{:<3} {:>9} {:>6} {:>5} {:>10}",
+            rank,
+            self.score,
+            self.lines,
+            self.level,
+            match self.ranking() {
+                Ranking::Score | Ranking::Survival => fmt_duration(self.time),
+                Ranking::Time => fmt_duration_ms(self.time),
+            }
+        )
+    }
+}
+
+impl std::fmt::Display for ScoreEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {} {} ",
+            self.mode,
+            self.rules,
+            self.score,
+            self.lines,
+            self.level,
+            self.time.as_millis(),
+            self.date
+        )?;
+        match self.splits.is_empty() {
+            // games without splits
+            true => write!(f, "-"),
+            false => {
+                let splits = self
+                    .splits
+                    .iter()
+                    .map(|s| s.as_millis().to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "{}", splits.join(","))
+            }
+        }
+    }
+}
+
+/// best scores of each mode, persisted in the data directory (one entry per line)
+pub struct HighScores {
+    entries: Vec<ScoreEntry>,
+}
+
+impl HighScores {
+    fn path() -> Option<PathBuf> {
+        Some(data_dir()?.join("scores"))
+    }
+
+    /// loads the saved scores, a missing or unreadable file means no scores yet
+    pub fn load() -> Self {
+        let entries = Self::path()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|content| content.lines().filter_map(ScoreEntry::parse).collect())
+            .unwrap_or_default();
+        Self { entries }
+    }
+
+    /// best results of `mode` under `rules`, best first (see `GameMode::ranking`)
+    pub fn top(&self, mode: GameMode, rules: Rules) -> Vec<&ScoreEntry> {
+        let mut top = self
+            .entries
+            .iter()
+            .filter(|e| e.mode == mode.name() && e.rules == rules.name())
+            .collect::<Vec<_>>();
+        top.sort_by(|a, b| ScoreEntry::compare(mode.ranking(), a, b));
+        top
+    }
+
+    /// adds a score and saves the file, returns whether it's the new best of its mode and rules
+    pub fn submit(&mut self, entry: ScoreEntry) -> Result<bool> {
+        let ranking = entry.ranking();
+        let is_best = (entry.score > 0 || ranking != Ranking::Score)
+            && self
+                .entries
+                .iter()
+                .filter(|e| e.same_table(&entry))
+                .all(|e| ScoreEntry::compare(ranking, &entry, e) == Ordering::Less);
+
+        self.entries.push(entry);
+        self.entries.sort_by(|a, b| {
+            (&a.mode, &a.rules)
+                .cmp(&(&b.mode, &b.rules))
+                .then_with(|| ScoreEntry::compare(a.ranking(), a, b))
+        });
+        // keep only the best scores of each mode and rules
+        let mut kept: Vec<ScoreEntry> = vec![];
+        for e in self.entries.drain(..) {
+            if kept.iter().filter(|k| k.same_table(&e)).count() < MAX_SCORES {
+                kept.push(e)
+            }
+        }
+        self.entries = kept;
+
+        let path = Self::path().ok_or(anyhow!("couldn't find the data directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = self
+            .entries
+            .iter()
+            .map(|e| format!("{e}\n"))
+            .collect::<String>();
+        fs::write(path, content)?;
+
+        Ok(is_best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(mode: &str, score: usize) -> ScoreEntry {
+        ScoreEntry {
+            mode: mode.to_string(),
+            rules: "guideline".to_string(),
+            score,
+            lines: 12,
+            level: 2,
+            time: Duration::from_millis(61_250),
+            date: 1_700_000_000,
+            splits: vec![],
+        }
+    }
+
+    #[test]
+    fn entries_read_back_as_written() {
+        let line = entry("MARATHON", 4200).to_string();
+        assert_eq!(line, "MARATHON guideline 4200 12 2 61250 1700000000 -");
+        let read = ScoreEntry::parse(&line).unwrap();
+        assert_eq!(read.to_string(), line);
+        assert!(ScoreEntry::parse("MARATHON guideline 4200 12").is_none());
+
+        let sprint = ScoreEntry {
+            splits: vec![Duration::from_millis(9_500), Duration::from_millis(20_125)],
+            ..entry("SPRINT20", 0)
+        };
+        let line = sprint.to_string();
+        assert!(line.ends_with(" 9500,20125"), "{line}");
+        let read = ScoreEntry::parse(&line).unwrap();
+        assert_eq!(read.splits, sprint.splits);
+        assert!(ScoreEntry::parse("SPRINT20 guideline 0 12 2 61250 1700000000 9500,x").is_none());
+    }
+
+    #[test]
+    fn top_scores_are_by_mode_highest_first() {
+        let scores = HighScores {
+            entries: vec![
+                entry("MARATHON", 10),
+                entry("SPRINT40", 99),
+                entry("MARATHON", 30),
+            ],
+        };
+        let top = scores.top(GameMode::Marathon, Rules::Guideline);
+        assert_eq!(top.iter().map(|e| e.score).collect::<Vec<_>>(), [30, 10]);
+    }
+
+    #[test]
+    fn scores_are_ranked_per_rules() {
+        let classic = ScoreEntry {
+            rules: "classic".to_string(),
+            ..entry("MARATHON", 50)
+        };
+        let scores = HighScores {
+            entries: vec![entry("MARATHON", 10), classic.clone()],
+        };
+        let top = |rules| {
+            scores
+                .top(GameMode::Marathon, rules)
+                .iter()
+                .map(|e| e.score)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(top(Rules::Guideline), [10]);
+        assert_eq!(top(Rules::Classic), [50]);
+        assert!(classic.same_table(&classic));
+        assert!(!classic.same_table(&entry("MARATHON", 50)));
+    }
+
+    #[test]
+    fn sprint_times_are_fastest_first() {
+        let timed = |ms| ScoreEntry {
+            time: Duration::from_millis(ms),
+            ..entry("SPRINT40", 0)
+        };
+        let scores = HighScores {
+            entries: vec![timed(50_000), timed(42_000), entry("MARATHON", 10)],
+        };
+        let top = scores.top(GameMode::Sprint(40), Rules::Guideline);
+        assert_eq!(
+            top.iter().map(|e| e.time.as_millis()).collect::<Vec<_>>(),
+            [42_000, 50_000]
+        );
+    }
+
+    #[test]
+    fn survivals_are_longest_first() {
+        let lasted = |secs, lines| ScoreEntry {
+            time: Duration::from_secs(secs),
+            lines,
+            ..entry("SURVIVAL", 0)
+        };
+        let scores = HighScores {
+            entries: vec![lasted(60, 10), lasted(90, 5), lasted(90, 8)],
+        };
+        let top = scores.top(GameMode::Survival, Rules::Guideline);
+        assert_eq!(
+            top.iter()
+                .map(|e| (e.time.as_secs(), e.lines))
+                .collect::<Vec<_>>(),
+            [(90, 8), (90, 5), (60, 10)]
+        );
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::config_dir;
+
+use super::{
+    graphics::ClearAnimation,
+    input::KeyBindings,
+    kicks::{self, KickTable, Kicks180},
+    menu::SettingItem,
+    rules::{RuleSet, Rules},
+    theme::Theme,
+    TICKS_PER_SECOND,
+};
+
+pub const DAS_RANGE: RangeInclusive<usize> = 0..=30;
+pub const ARR_RANGE: RangeInclusive<usize> = 0..=10;
+pub const SOFT_DROP_FACTOR_RANGE: RangeInclusive<usize> = 1..=40;
+pub const ARE_RANGE: RangeInclusive<usize> = 0..=30;
+pub const LINE_CLEAR_DELAY_RANGE: RangeInclusive<usize> = 0..=60;
+pub const MESSINESS_RANGE: RangeInclusive<usize> = 0..=100;
+pub const NEXT_COUNT_RANGE: RangeInclusive<usize> = 0..=4;
+pub const FPS_CAPS: [usize; 5] = [10, 15, 20, 30, 60];
+pub const BOARD_WIDTH_RANGE: RangeInclusive<usize> = 4..=20;
+pub const BOARD_HEIGHT_RANGE: RangeInclusive<usize> = 8..=40;
+
+#[derive(Clone)]
+pub struct Settings {
+    /// rule set of the games, unless their mode has its own
+    pub rules: Rules,
+    /// the rules of `Rules::Custom`, read from `rules.toml` next to the config file, `None`
+    /// without that file
+    pub custom_rules: Option<RuleSet>,
+    /// delayed auto shift: ticks a move key has to be held before the piece starts sliding
+    pub das: usize,
+    /// auto repeat rate: ticks between two shifts once sliding, 0 teleports the piece to the wall
+    pub arr: usize,
+    /// how many times faster than gravity a soft drop is
+    pub soft_drop_factor: usize,
+    /// show where the piece will land
+    pub ghost: bool,
+    /// number of next pieces shown
+    pub next_count: usize,
+    pub theme: Theme,
+    /// maximum number of frames rendered per second (the game itself always runs at `TICKS_PER_SECOND`)
+    pub fps: usize,
+    /// resume the game when the terminal regains focus (if it was paused because focus was lost)
+    pub auto_resume: bool,
+    /// top out when a piece locks partly above the visible field, not only fully
+    pub partial_lock_out: bool,
+    /// kicks tried when a 180° rotation doesn't fit in place
+    pub kicks_180: Kicks180,
+    /// the 180° kicks of `Kicks180::Custom`
+    pub custom_kicks_180: KickTable,
+    /// entry delay: ticks between a piece locking and the next one spawning
+    pub are: usize,
+    /// initial rotation/hold: rotate and hold keys pressed during the entry delay apply to the
+    /// next piece as it spawns
+    pub irs: bool,
+    /// ticks the cleared rows stay on the board before the stack collapses
+    pub line_clear_delay: usize,
+    pub clear_animation: ClearAnimation,
+    /// chance (percent) that the hole of a garbage row moves from the one of the row above
+    pub messiness: usize,
+    /// whether pieces fall on their own in Zen, they only move when dropped otherwise
+    pub zen_gravity: bool,
+    /// board size in blocks (visible rows only), the same on every terminal
+    pub width: usize,
+    pub height: usize,
+    pub keys: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            rules: Rules::Guideline,
+            custom_rules: None,
+            das: 10,
+            arr: 2,
+            soft_drop_factor: 20,
+            ghost: true,
+            next_count: 3,
+            theme: Theme::Default,
+            fps: 60,
+            auto_resume: false,
+            partial_lock_out: false,
+            kicks_180: Kicks180::SrsPlus,
+            custom_kicks_180: Kicks180::None.table(&Default::default()),
+            are: 0,
+            irs: false,
+            line_clear_delay: 15,
+            clear_animation: ClearAnimation::Flash,
+            messiness: 30,
+            zen_gravity: false,
+            width: 10,
+            height: 20,
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+/// a key list of the config file, `hold = "c"` and `hold = ["c", "tab"]` are both accepted
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum KeyNames {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// content of `config.toml`, as written by the user
+#[derive(Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    rules: String,
+    das: usize,
+    arr: usize,
+    soft_drop_factor: usize,
+    ghost: bool,
+    next_count: usize,
+    theme: String,
+    fps: usize,
+    auto_resume: bool,
+    partial_lock_out: bool,
+    kicks_180: String,
+    /// starting and ending orientations (`0>2`...) -> kicks as [x, y] with y going up
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    custom_kicks_180: kicks::YUpTable,
+    are: usize,
+    irs: bool,
+    line_clear_delay: usize,
+    clear_animation: String,
+    messiness: usize,
+    zen_gravity: bool,
+    width: usize,
+    height: usize,
+    keys: BTreeMap<String, KeyNames>,
+}
+
+impl Default for ConfigFile {
+    fn default() -> Self {
+        let mut config = ConfigFile::from(&Settings::default());
+        // actions missing from the file keep their default keys, see `KeyBindings::from_names`
+        config.keys.clear();
+        config
+    }
+}
+
+impl From<&Settings> for ConfigFile {
+    fn from(s: &Settings) -> Self {
+        Self {
+            rules: s.rules.name().to_string(),
+            das: s.das,
+            arr: s.arr,
+            soft_drop_factor: s.soft_drop_factor,
+            ghost: s.ghost,
+            next_count: s.next_count,
+            theme: s.theme.name().to_string(),
+            fps: s.fps,
+            auto_resume: s.auto_resume,
+            partial_lock_out: s.partial_lock_out,
+            kicks_180: s.kicks_180.name().to_string(),
+            // only written when it's not the default one
+            custom_kicks_180: match s.custom_kicks_180 == Settings::default().custom_kicks_180 {
+                true => BTreeMap::new(),
+                false => kicks::to_y_up_table(&s.custom_kicks_180),
+            },
+            are: s.are,
+            irs: s.irs,
+            line_clear_delay: s.line_clear_delay,
+            clear_animation: s.clear_animation.name().to_string(),
+            messiness: s.messiness,
+            zen_gravity: s.zen_gravity,
+            width: s.width,
+            height: s.height,
+            keys: s
+                .keys
+                .to_names()
+                .into_iter()
+                .map(|(action, names)| (action, KeyNames::Many(names)))
+                .collect(),
+        }
+    }
+}
+
+impl Settings {
+    /// `$XDG_CONFIG_HOME/corrodis/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        Some(config_dir()?.join("config.toml"))
+    }
+
+    /// the custom rules file, next to the config file at `path`
+    pub fn rules_path(path: &Path) -> PathBuf {
+        path.with_file_name("rules.toml")
+    }
+
+    /// reads the config file, a missing file means the default settings
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|why| anyhow!("couldn't read config file {}: {why}", path.display()))?;
+        let config: ConfigFile = toml::from_str(&content)
+            .map_err(|why| anyhow!("invalid config file {}:\n{why}", path.display()))?;
+
+        let mut errors = vec![];
+        let mut check_range = |name: &str, value: usize, range: RangeInclusive<usize>| {
+            if !range.contains(&value) {
+                errors.push(format!(
+                    "`{name}` must be between {} and {}, got {value}",
+                    range.start(),
+                    range.end()
+                ))
+            }
+        };
+        check_range("das", config.das, DAS_RANGE);
+        check_range("arr", config.arr, ARR_RANGE);
+        check_range(
+            "soft_drop_factor",
+            config.soft_drop_factor,
+            SOFT_DROP_FACTOR_RANGE,
+        );
+        check_range("next_count", config.next_count, NEXT_COUNT_RANGE);
+        check_range("are", config.are, ARE_RANGE);
+        check_range(
+            "line_clear_delay",
+            config.line_clear_delay,
+            LINE_CLEAR_DELAY_RANGE,
+        );
+        check_range("messiness", config.messiness, MESSINESS_RANGE);
+        check_range("width", config.width, BOARD_WIDTH_RANGE);
+        check_range("height", config.height, BOARD_HEIGHT_RANGE);
+        if !FPS_CAPS.contains(&config.fps) {
+            errors.push(format!(
+                "`fps` must be one of {FPS_CAPS:?}, got {}",
+                config.fps
+            ));
+        }
+        let theme = Theme::from_name(&config.theme);
+        if theme.is_none() {
+            errors.push(format!(
+                "unknown theme `{}`, expected one of {}",
+                config.theme,
+                Theme::ALL.map(|t| t.name()).join(", ")
+            ));
+        }
+
+        let rules = Rules::from_name(&config.rules);
+        if rules.is_none() {
+            errors.push(format!(
+                "unknown rules `{}`, expected one of {}",
+                config.rules,
+                Rules::ALL.map(|r| r.name()).join(", ")
+            ));
+        }
+
+        let clear_animation = ClearAnimation::from_name(&config.clear_animation);
+        if clear_animation.is_none() {
+            errors.push(format!(
+                "unknown clear animation `{}`, expected one of {}",
+                config.clear_animation,
+                ClearAnimation::ALL.map(|a| a.name()).join(", ")
+            ));
+        }
+
+        let kicks_180 = Kicks180::from_name(&config.kicks_180);
+        if kicks_180.is_none() {
+            errors.push(format!(
+                "unknown 180° kick table `{}`, expected one of {}",
+                config.kicks_180,
+                Kicks180::ALL.map(|k| k.name()).join(", ")
+            ));
+        }
+        let custom_kicks_180 = kicks::from_y_up_table(&config.custom_kicks_180, &mut errors);
+
+        let mut custom_rules = None;
+        let rules_path = Self::rules_path(path);
+        if rules_path.exists() {
+            match RuleSet::load(&rules_path) {
+                Ok(rules) => custom_rules = Some(rules),
+                Err(why) => errors.push(why.to_string()),
+            }
+        } else if rules == Some(Rules::Custom) {
+            errors.push(format!(
+                "custom rules need a rules file, {} doesn't exist",
+                rules_path.display()
+            ));
+        }
+
+        let key_names = config
+            .keys
+            .into_iter()
+            .map(|(action, names)| match names {
+                KeyNames::One(name) => (action, vec![name]),
+                KeyNames::Many(names) => (action, names),
+            })
+            .collect();
+        let keys = KeyBindings::from_names(&key_names).unwrap_or_else(|key_errors| {
+            errors.extend(key_errors);
+            KeyBindings::default()
+        });
+
+        match (rules, theme, clear_animation, kicks_180) {
+            (Some(rules), Some(theme), Some(clear_animation), Some(kicks_180))
+                if errors.is_empty() =>
+            {
+                Ok(Self {
+                    rules,
+                    custom_rules,
+                    das: config.das,
+                    arr: config.arr,
+                    soft_drop_factor: config.soft_drop_factor,
+                    ghost: config.ghost,
+                    next_count: config.next_count,
+                    theme,
+                    fps: config.fps,
+                    auto_resume: config.auto_resume,
+                    partial_lock_out: config.partial_lock_out,
+                    kicks_180,
+                    custom_kicks_180,
+                    are: config.are,
+                    irs: config.irs,
+                    line_clear_delay: config.line_clear_delay,
+                    clear_animation,
+                    messiness: config.messiness,
+                    zen_gravity: config.zen_gravity,
+                    width: config.width,
+                    height: config.height,
+                    keys,
+                })
+            }
+            _ => Err(anyhow!(
+                "invalid config file {}:\n{}",
+                path.display(),
+                errors
+                    .iter()
+                    .map(|e| format!("  - {e}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )),
+        }
+    }
+
+    /// writes the settings to `path` through a temporary file, so a crash can't leave a half written config
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = toml::to_string_pretty(&ConfigFile::from(self))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let tmp = path.with_extension("toml.tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// value of a setting as displayed on the settings screen
+    pub fn value(&self, item: SettingItem) -> String {
+        let on_off = |b: bool| if b { "ON" } else { "OFF" }.to_string();
+        let ms = |ticks: usize| ticks * 1000 / TICKS_PER_SECOND;
+        match item {
+            SettingItem::Rules => self.rules.name().to_uppercase(),
+            SettingItem::Das => format!("{}F ({}MS)", self.das, ms(self.das)),
+            SettingItem::Arr => format!("{}F ({}MS)", self.arr, ms(self.arr)),
+            SettingItem::SoftDropFactor => format!("X{}", self.soft_drop_factor),
+            SettingItem::Ghost => on_off(self.ghost),
+            SettingItem::NextCount => self.next_count.to_string(),
+            SettingItem::Theme => self.theme.name().to_uppercase(),
+            SettingItem::Fps => self.fps.to_string(),
+            SettingItem::AutoResume => on_off(self.auto_resume),
+            SettingItem::Kicks180 => self.kicks_180.name().to_uppercase(),
+            SettingItem::PartialLockOut => match self.partial_lock_out {
+                true => "PARTIAL".to_string(),
+                false => "FULL".to_string(),
+            },
+            SettingItem::Are => format!("{}F ({}MS)", self.are, ms(self.are)),
+            SettingItem::Irs => on_off(self.irs),
+            SettingItem::LineClearDelay => {
+                format!(
+                    "{}F ({}MS)",
+                    self.line_clear_delay,
+                    ms(self.line_clear_delay)
+                )
+            }
+            SettingItem::ClearAnimation => self.clear_animation.name().to_uppercase(),
+            SettingItem::Messiness => format!("{}%", self.messiness),
+            SettingItem::ZenGravity => on_off(self.zen_gravity),
+            SettingItem::KeyBindings | SettingItem::Back => String::new(),
+        }
+    }
+
+    /// changes a setting by `step` (left/right on the settings screen), values don't wrap
+    pub fn adjust(&mut self, item: SettingItem, step: isize) {
+        let step_in = |value: usize, range: RangeInclusive<usize>| {
+            (value as isize + step).clamp(*range.start() as isize, *range.end() as isize) as usize
+        };
+        match item {
+            SettingItem::Rules => {
+                // custom rules can't be picked without a rules file
+                let choices = Rules::ALL
+                    .into_iter()
+                    .filter(|r| *r != Rules::Custom || self.custom_rules.is_some())
+                    .collect::<Vec<_>>();
+                let i = choices.iter().position(|r| *r == self.rules).unwrap_or(0);
+                self.rules = choices[step_in(i, 0..=choices.len() - 1)]
+            }
+            SettingItem::Das => self.das = step_in(self.das, DAS_RANGE),
+            SettingItem::Arr => self.arr = step_in(self.arr, ARR_RANGE),
+            SettingItem::SoftDropFactor => {
+                self.soft_drop_factor = step_in(self.soft_drop_factor, SOFT_DROP_FACTOR_RANGE)
+            }
+            SettingItem::Ghost => self.ghost = !self.ghost,
+            SettingItem::NextCount => self.next_count = step_in(self.next_count, NEXT_COUNT_RANGE),
+            SettingItem::Theme => {
+                let i = Theme::ALL
+                    .iter()
+                    .position(|t| *t == self.theme)
+                    .unwrap_or(0);
+                self.theme = Theme::ALL[step_in(i, 0..=Theme::ALL.len() - 1)]
+            }
+            SettingItem::Fps => {
+                let i = FPS_CAPS.iter().position(|f| *f == self.fps).unwrap_or(0);
+                self.fps = FPS_CAPS[step_in(i, 0..=FPS_CAPS.len() - 1)]
+            }
+            SettingItem::AutoResume => self.auto_resume = !self.auto_resume,
+            SettingItem::PartialLockOut => self.partial_lock_out = !self.partial_lock_out,
+            SettingItem::Kicks180 => {
+                let i = Kicks180::ALL
+                    .iter()
+                    .position(|k| *k == self.kicks_180)
+                    .unwrap_or(0);
+                self.kicks_180 = Kicks180::ALL[step_in(i, 0..=Kicks180::ALL.len() - 1)]
+            }
+            SettingItem::Are => self.are = step_in(self.are, ARE_RANGE),
+            SettingItem::Irs => self.irs = !self.irs,
+            SettingItem::ZenGravity => self.zen_gravity = !self.zen_gravity,
+            SettingItem::LineClearDelay => {
+                self.line_clear_delay = step_in(self.line_clear_delay, LINE_CLEAR_DELAY_RANGE)
+            }
+            SettingItem::Messiness => {
+                // 5% steps, 101 values are too many to go through one by one
+                self.messiness = (self.messiness as isize + 5 * step).clamp(
+                    *MESSINESS_RANGE.start() as isize,
+                    *MESSINESS_RANGE.end() as isize,
+                ) as usize
+            }
+            SettingItem::ClearAnimation => {
+                let i = ClearAnimation::ALL
+                    .iter()
+                    .position(|a| *a == self.clear_animation)
+                    .unwrap_or(0);
+                self.clear_animation =
+                    ClearAnimation::ALL[step_in(i, 0..=ClearAnimation::ALL.len() - 1)]
+            }
+            SettingItem::KeyBindings | SettingItem::Back => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::input::Action;
+    use termion::event::Key;
+
+    #[test]
+    fn adjusting_stops_at_the_bounds() {
+        let mut settings = Settings::default();
+        settings.adjust(SettingItem::Das, -100);
+        assert_eq!(settings.das, *DAS_RANGE.start());
+        settings.adjust(SettingItem::Fps, 1);
+        assert_eq!(settings.fps, 60);
+        settings.adjust(SettingItem::Fps, -1);
+        assert_eq!(settings.fps, 30);
+        settings.adjust(SettingItem::Ghost, 1);
+        assert_eq!(settings.value(SettingItem::Ghost), "OFF");
+    }
+
+    #[test]
+    fn custom_rules_need_a_rules_file() {
+        let mut settings = Settings::default();
+        settings.adjust(SettingItem::Rules, 10);
+        assert!(settings.rules == Rules::Master);
+        settings.custom_rules = Some(RuleSet::default());
+        settings.adjust(SettingItem::Rules, 1);
+        assert!(settings.rules == Rules::Custom);
+    }
+
+    #[test]
+    fn saved_settings_load_back() {
+        let path = std::env::temp_dir()
+            .join(format!("corrodis-test-{}", std::process::id()))
+            .join("config.toml");
+        let mut settings = Settings {
+            das: 7,
+            theme: Theme::ALL[Theme::ALL.len() - 1],
+            ..Settings::default()
+        };
+        settings.keys.rebind(Action::Hold, Key::Char('q')).unwrap();
+        settings.save(&path).unwrap();
+
+        let loaded = Settings::load(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert_eq!(loaded.das, 7);
+        assert!(loaded.theme == settings.theme);
+        assert_eq!(loaded.keys.to_names(), settings.keys.to_names());
+    }
+
+    #[test]
+    fn every_invalid_value_is_reported() {
+        let path = std::env::temp_dir()
+            .join(format!("corrodis-test-invalid-{}", std::process::id()))
+            .join("config.toml");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "das = 99\nfps = 42\ntheme = \"nope\"\n").unwrap();
+
+        let error = Settings::load(&path).err().unwrap().to_string();
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        assert!(error.contains("`das` must be between 0 and 30, got 99"));
+        assert!(error.contains("`fps` must be one of [10, 15, 20, 30, 60], got 42"));
+        assert!(error.contains("unknown theme `nope`"));
+    }
+}
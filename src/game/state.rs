@@ -0,0 +1,560 @@
+use nanorand::WyRand;
+
+use crate::utils::Ivec2;
+
+use super::{
+    board::{Board, TopOut, HIDDEN_ROWS},
+    finesse::PieceInputs,
+    input::Action,
+    kicks::{self, KickTable},
+    mode::{Ending, GameMode},
+    puzzle::Goal,
+    replay::Replay,
+    rules::{Gravity, LockReset, RuleSet},
+    stats::Stats,
+    tspin,
+    types::{LastMove, Tetrominoe, TetrominoeType},
+    LineClear, NEXT_QUEUE,
+};
+
+/// the engine of a game: everything that plays out the same from the same replay, without the
+/// terminal, the menus or the keys held by the player. Cloning it snapshots the game
+#[derive(Clone)]
+pub struct GameState {
+    pub board: Board,
+    /// rules of the game (the settings may change during it)
+    pub rules: RuleSet,
+    /// chance (percent) that the hole of a garbage row isn't under the one of the row above
+    pub messiness: usize,
+    /// ticks left before the next piece spawns, `None` while a piece is falling
+    pub entry_delay: Option<usize>,
+    /// rotation and hold buffered during the entry delay (IRS/IHS)
+    initial_rotation: Option<Action>,
+    initial_hold: bool,
+    pub line_clear: Option<LineClear>,
+    /// garbage rows risen so far and tick the next one rises on, in Survival
+    pub garbage_risen: usize,
+    pub next_rise: Option<u64>,
+
+    pub tetrominoe: Tetrominoe,
+    pub next_tetrominoes: Vec<Tetrominoe>,
+    pub held: Option<Tetrominoe>,
+    /// a piece can only be held once until it locks
+    hold_used: bool,
+
+    pub stats: Stats,
+    /// inputs spent on the falling piece, for the finesse faults
+    pub finesse: PieceInputs,
+    /// whether the finesse faults are counted, only key presses of a player tell them
+    pub judges_finesse: bool,
+    /// lines shown after a T-spin (or a cleared stack) and the tick they appeared on
+    pub callout: Option<(Vec<&'static str>, u64)>,
+    pub mode: GameMode,
+    /// goal of a puzzle, whose queue is fixed instead of refilled by the randomizer
+    pub goal: Option<Goal>,
+
+    /// every random draw of a game comes from this generator, so a seed is enough to replay it
+    rng: WyRand,
+    /// number of frames computed since the start of the game
+    pub tick: u64,
+    /// how the game ended when a hard drop locked the piece, told by the next frame
+    ending: Option<Ending>,
+}
+
+impl GameState {
+    /// the game `replay` starts with: its board, garbage and first pieces
+    pub fn new(replay: &Replay) -> Self {
+        let mut state = Self {
+            board: Board::new(replay.width, replay.height),
+            rules: replay.rules.clone(),
+            messiness: replay.messiness,
+            entry_delay: None,
+            initial_rotation: None,
+            initial_hold: false,
+            line_clear: None,
+            garbage_risen: 0,
+            next_rise: replay.mode.rise_interval(0),
+            // replaced by the first piece of the queue below
+            tetrominoe: Tetrominoe::new(TetrominoeType::I, replay.rules.rotation),
+            next_tetrominoes: vec![],
+            held: None,
+            hold_used: false,
+            stats: Stats::new(replay.level, replay.rules.scoring),
+            finesse: PieceInputs::default(),
+            judges_finesse: false,
+            callout: None,
+            mode: replay.mode,
+            goal: replay.puzzle.as_ref().map(|p| p.goal),
+            rng: WyRand::new_seed(replay.seed),
+            tick: 0,
+            ending: None,
+        };
+
+        let garbage = replay.mode.garbage_rows(replay.height);
+        state
+            .board
+            .add_garbage(garbage, replay.messiness, &mut state.rng);
+        match &replay.puzzle {
+            Some(puzzle) => {
+                let rotation = replay.rules.rotation;
+                state.board.set_bottom_rows(&puzzle.rows);
+                state.next_tetrominoes = puzzle
+                    .queue
+                    .iter()
+                    .map(|t| Tetrominoe::new(*t, rotation))
+                    .collect();
+                state.held = puzzle.hold.map(|t| Tetrominoe::new(t, rotation));
+            }
+            None => {
+                for _ in 0..=NEXT_QUEUE {
+                    let piece = state.new_tetrominoe();
+                    state.next_tetrominoes.push(piece);
+                }
+            }
+        }
+        let first = state.next_tetrominoes.remove(0);
+        state.spawn(first);
+        state
+    }
+
+    /// brings the next piece in after a lock, once the entry delay (if any) is over
+    fn pick_next_tetrominoe(&mut self) {
+        self.hold_used = false;
+        match self.rules.are(self.stats.level) {
+            0 => self.take_next_tetrominoe(),
+            are => self.entry_delay = Some(are),
+        }
+    }
+
+    /// ends the entry delay: spawns the next piece, held first and then rotated if the player
+    /// asked for it in the meantime
+    fn end_entry_delay(&mut self) {
+        self.entry_delay = None;
+        self.take_next_tetrominoe();
+        if std::mem::take(&mut self.initial_hold) {
+            self.hold_tetrominoe();
+        }
+        if let Some(action) = self.initial_rotation.take() {
+            self.apply(action);
+        }
+    }
+
+    /// whether a piece is falling, there's none during the line clear and entry delays
+    pub fn piece_in_play(&self) -> bool {
+        self.line_clear.is_none() && self.entry_delay.is_none()
+    }
+
+    /// a piece picked by the randomizer of the rules, to go at the end of the next queue
+    fn new_tetrominoe(&mut self) -> Tetrominoe {
+        let previous = self.next_tetrominoes.last().map(|p| p.ttype);
+        let ttype = self.rules.randomizer.next(&mut self.rng, previous);
+        Tetrominoe::new(ttype, self.rules.rotation)
+    }
+
+    /// spawns the first piece of the next queue, or the held one once a puzzle queue is empty
+    fn take_next_tetrominoe(&mut self) {
+        if self.next_tetrominoes.is_empty() {
+            if let Some(held) = self.held.take() {
+                self.spawn(held);
+            }
+            return;
+        }
+        let next = self.next_tetrominoes.remove(0);
+        if self.goal.is_none() {
+            let piece = self.new_tetrominoe();
+            self.next_tetrominoes.push(piece);
+        }
+        self.spawn(next);
+    }
+
+    /// pieces a puzzle has left, the falling one included
+    pub fn pieces_left(&self) -> usize {
+        self.next_tetrominoes.len() + self.held.is_some() as usize + self.piece_in_play() as usize
+    }
+
+    /// makes `piece` the falling one, centered (leaning left) right above the visible field.
+    /// As in the guideline it drops one row right away if nothing is in the way
+    fn spawn(&mut self, mut piece: Tetrominoe) {
+        let (min_x, max_x, _, max_y) = piece.bounds();
+        let width = (max_x - min_x + 1) as usize;
+        piece.pos = Ivec2::new(
+            ((self.board.width - width) / 2) as isize - min_x,
+            HIDDEN_ROWS as isize - 1 - max_y,
+        );
+        piece.fall = 0;
+        self.tetrominoe = piece;
+        self.try_move(0, 1);
+        if self.gravity().is_20g() {
+            while self.try_move(0, 1) {}
+        }
+        self.finesse = PieceInputs::new(&self.tetrominoe);
+    }
+
+    /// applies an action acting on the piece, the other ones are up to the caller
+    pub fn apply(&mut self, action: Action) {
+        if self.ending.is_some() || (action == Action::Rotate180 && !self.rules.rotate_180) {
+            return;
+        }
+        // no piece to act on during the entry delay, rotating and holding are kept for the next one
+        if !self.piece_in_play() {
+            match action {
+                Action::RotateCcw | Action::RotateCw | Action::Rotate180 if self.rules.irs => {
+                    self.initial_rotation = Some(action)
+                }
+                Action::Hold if self.rules.irs => self.initial_hold = true,
+                _ => {}
+            }
+            return;
+        }
+        match action {
+            Action::RotateCcw => {
+                self.try_rotate(self.tetrominoe.rotated(true));
+            }
+            Action::RotateCw => {
+                self.try_rotate(self.tetrominoe.rotated(false));
+            }
+            Action::Rotate180 => {
+                self.try_rotate(self.tetrominoe.rotated_180());
+            }
+            Action::MoveLeft => {
+                self.try_move(-1, 0);
+            }
+            Action::MoveRight => {
+                self.try_move(1, 0);
+            }
+            Action::SoftDrop => {
+                if self.try_move(0, 1) {
+                    self.finesse.soft_dropped = true;
+                    self.tetrominoe.fall = 0;
+                    self.stats.on_drop(1, false);
+                }
+            }
+            Action::HardDrop => {
+                let rows = self.board.drop_distance(&self.tetrominoe.cells());
+                self.tetrominoe.pos.y += rows as isize;
+                if rows > 0 {
+                    self.tetrominoe.last_move = Some(LastMove::Shift);
+                }
+                self.stats.on_drop(rows, true);
+                self.ending = self.lock_piece();
+            }
+            Action::Hold => self.hold_tetrominoe(),
+            Action::Undo
+            | Action::Redo
+            | Action::Pause
+            | Action::Restart
+            | Action::Retry
+            | Action::Quit => {}
+        }
+    }
+
+    /// moves the falling piece if it fits there, returns whether it moved
+    fn try_move(&mut self, dx: isize, dy: isize) -> bool {
+        let moved = self.tetrominoe.moved(dx, dy);
+        let fits = self.board.fits(&moved.cells());
+        if fits {
+            self.tetrominoe = moved;
+            self.tetrominoe.last_move = Some(LastMove::Shift);
+            self.reset_lock_delay();
+        }
+        fits
+    }
+
+    /// puts the falling piece in the orientation of `rotated`, trying the kicks of the turn in
+    /// order when it doesn't fit in place. Returns whether it turned
+    fn try_rotate(&mut self, rotated: Tetrominoe) -> bool {
+        let from = self.tetrominoe.rotation;
+        let half_turn = (rotated.rotation + 4 - from) % 4 == 2;
+        let Some((kicked, kick)) = kicks::first_fit(&self.board, from, &rotated, self.kicks_180())
+        else {
+            return false;
+        };
+        self.tetrominoe = kicked;
+        self.tetrominoe.last_move = Some(LastMove::Rotation { kick, half_turn });
+        self.reset_lock_delay();
+        true
+    }
+
+    /// called after the falling piece moved, restarts the lock delay as the rules say
+    fn reset_lock_delay(&mut self) {
+        let (reset, max_resets) = (self.rules.lock_reset, self.rules.max_lock_resets);
+        let piece = &mut self.tetrominoe;
+        let bottom = piece.bottom();
+        if bottom > piece.lowest {
+            piece.lowest = bottom;
+            if reset != LockReset::None {
+                piece.lock_resets = 0;
+                piece.lock_ticks = 0;
+            }
+        } else if reset == LockReset::Move && piece.lock_ticks > 0 && piece.lock_resets < max_resets
+        {
+            piece.lock_resets += 1;
+            piece.lock_ticks = 0;
+        }
+    }
+
+    /// swaps the falling piece with the held one (or the next one if nothing is held), once per piece
+    fn hold_tetrominoe(&mut self) {
+        // nothing to swap with on the last piece of a puzzle
+        if self.hold_used
+            || !self.rules.hold
+            || (self.held.is_none() && self.next_tetrominoes.is_empty())
+        {
+            return;
+        }
+        let mut current = self.tetrominoe.clone();
+        current.reset();
+        match self.held.replace(current) {
+            Some(held) => self.spawn(held),
+            None => self.take_next_tetrominoe(),
+        }
+        self.hold_used = true;
+    }
+
+    /// moves the game forward by a tick, returns how the game ended if it did
+    pub fn compute_next_frame(&mut self) -> Option<Ending> {
+        if let Some(ending) = self.ending.take() {
+            return Some(ending);
+        }
+        self.stats.ticks += 1;
+        if self.mode.is_finished(&self.stats, &self.board, &[]) {
+            return Some(Ending::Finished);
+        }
+        if let Some(top_out) = self.rise_garbage() {
+            return Some(Ending::TopOut(top_out));
+        }
+        // the stack collapses once the cleared rows are done animating
+        if let Some(clear) = &mut self.line_clear {
+            clear.ticks += 1;
+            if clear.ticks < self.rules.line_clear_delay {
+                return None;
+            }
+            let rows = std::mem::take(&mut clear.rows);
+            self.line_clear = None;
+            self.board.remove_rows(&rows);
+            return self.finish_lock();
+        }
+        match self.entry_delay {
+            Some(ticks) if ticks > 1 => {
+                self.entry_delay = Some(ticks - 1);
+                return None;
+            }
+            Some(_) => self.end_entry_delay(),
+            None => {}
+        }
+
+        // the new piece (spawned or taken from the hold) has no room
+        if !self.board.fits(&self.tetrominoe.cells()) {
+            if let Some(ending) = self.top_out(TopOut::Block) {
+                return Some(ending);
+            }
+        }
+
+        let gravity = self.gravity();
+        if gravity.is_20g() {
+            while self.try_move(0, 1) {}
+        } else {
+            self.tetrominoe.fall += gravity.rows;
+            while self.tetrominoe.fall >= gravity.ticks {
+                self.tetrominoe.fall -= gravity.ticks;
+                if !self.try_move(0, 1) {
+                    self.tetrominoe.fall = 0;
+                }
+            }
+        }
+
+        // on the ground, the piece locks once the lock delay is over
+        if !self.board.fits(&self.tetrominoe.moved(0, 1).cells()) {
+            self.tetrominoe.lock_ticks += 1;
+            if self.tetrominoe.lock_ticks >= self.lock_delay() {
+                return self.lock_piece();
+            }
+        }
+        None
+    }
+
+    /// locks the falling piece where it is, then clears its rows or brings the next piece in.
+    /// Returns how the game ended if it did
+    fn lock_piece(&mut self) -> Option<Ending> {
+        let spin = match self.rules.t_spins {
+            true => tspin::detect(&self.board, &self.tetrominoe),
+            false => None,
+        };
+        if self.judges_finesse {
+            self.stats.finesse_faults += self.finesse.faults(
+                &self.board,
+                &self.tetrominoe,
+                self.kicks_180(),
+                self.rules.rotate_180,
+            );
+        }
+        self.board
+            .lock(&self.tetrominoe.cells(), self.tetrominoe.ttype);
+        let rows = self.board.full_rows();
+        self.stats.on_lock(rows.len(), spin);
+        if let Some(spin) = spin {
+            self.callout = Some((spin.callout(rows.len()), self.tick));
+        }
+        // the clock stops on the lock, not once the rows are gone
+        let solved = self
+            .goal
+            .is_some_and(|goal| goal.is_reached(&self.stats, &self.board, &rows, spin));
+        if solved || self.mode.is_finished(&self.stats, &self.board, &rows) {
+            return Some(Ending::Finished);
+        }
+        if !rows.is_empty() && self.rules.line_clear_delay > 0 {
+            self.line_clear = Some(LineClear { rows, ticks: 0 });
+            return None;
+        }
+        self.board.remove_rows(&rows);
+        self.finish_lock()
+    }
+
+    /// 180° kicks, `None` when the rules have no kicks at all
+    pub fn kicks_180(&self) -> Option<&KickTable> {
+        self.rules.kicks.then_some(&self.rules.kicks_180)
+    }
+
+    pub fn gravity(&self) -> Gravity {
+        self.rules.gravity(self.stats.level)
+    }
+
+    fn lock_delay(&self) -> usize {
+        self.rules.lock_delay(self.stats.level)
+    }
+
+    /// pushes the stack up by a garbage row once it's time to, returns how the game was lost if
+    /// it was. Rows wait for the line clear delay to end
+    fn rise_garbage(&mut self) -> Option<TopOut> {
+        if self.next_rise.is_none_or(|tick| self.tick < tick) || self.line_clear.is_some() {
+            return None;
+        }
+        self.garbage_risen += 1;
+        self.next_rise = self
+            .mode
+            .rise_interval(self.garbage_risen)
+            .map(|ticks| self.tick + ticks);
+
+        if self.board.add_garbage(1, self.messiness, &mut self.rng) {
+            return Some(TopOut::Garbage);
+        }
+        // a falling piece caught by the stack rides up with it
+        if self.piece_in_play() && !self.board.fits(&self.tetrominoe.cells()) {
+            self.tetrominoe.pos.y -= 1;
+            if !self.board.fits(&self.tetrominoe.cells()) {
+                return Some(TopOut::Garbage);
+            }
+        }
+        None
+    }
+
+    /// once the locked piece's rows are cleared: tops out or brings the next piece in
+    fn finish_lock(&mut self) -> Option<Ending> {
+        let top_out = if self.tetrominoe.cells().iter().all(|c| Board::is_hidden(*c)) {
+            Some(TopOut::Lock)
+        // blocks pushed down by the cleared rows are fine
+        } else if self.rules.partial_lock_out && self.board.has_hidden_blocks() {
+            Some(TopOut::PartialLock)
+        } else {
+            None
+        };
+        if let Some(ending) = top_out.and_then(|top_out| self.top_out(top_out)) {
+            return Some(ending);
+        }
+        if self.next_tetrominoes.is_empty() && self.held.is_none() {
+            return Some(Ending::Failed);
+        }
+        self.pick_next_tetrominoe();
+        None
+    }
+
+    /// ends the game, or clears the stack in the modes without top out (`None` then)
+    fn top_out(&mut self, top_out: TopOut) -> Option<Ending> {
+        if self.mode.tops_out() {
+            return Some(Ending::TopOut(top_out));
+        }
+        self.board.clear();
+        self.callout = Some((vec!["STACK", "CLEARED"], self.tick));
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{board::Block, puzzle::Puzzle};
+
+    fn new_game(mode: GameMode) -> GameState {
+        GameState::new(&Replay::new(mode, 1, 1, 10, 20))
+    }
+
+    #[test]
+    fn hard_drops_lock_right_away() {
+        let mut game = new_game(GameMode::Marathon);
+        let ttype = game.tetrominoe.ttype;
+        game.apply(Action::HardDrop);
+        assert_eq!(game.stats.pieces, 1);
+        let bottom = game.board.visible_rows().last().unwrap();
+        assert!(bottom.contains(&Some(Block::Piece(ttype))));
+    }
+
+    #[test]
+    fn snapshots_play_out_the_same() {
+        let mut game = new_game(GameMode::Marathon);
+        let mut snapshot = game.clone();
+        for state in [&mut game, &mut snapshot] {
+            for action in [Action::RotateCw, Action::MoveLeft, Action::HardDrop] {
+                state.apply(action);
+                state.compute_next_frame();
+            }
+        }
+        assert!(game.board.visible_rows() == snapshot.board.visible_rows());
+        assert_eq!(game.tetrominoe.ttype, snapshot.tetrominoe.ttype);
+        assert_eq!(game.stats.score, snapshot.stats.score);
+    }
+
+    #[test]
+    fn zen_clears_the_stack_instead_of_topping_out() {
+        let mut game = new_game(GameMode::Zen);
+        for _ in 0..100 {
+            game.apply(Action::HardDrop);
+            assert!(game.compute_next_frame().is_none());
+            while !game.piece_in_play() {
+                assert!(game.compute_next_frame().is_none());
+            }
+        }
+        assert!(game
+            .callout
+            .is_some_and(|(lines, _)| lines == ["STACK", "CLEARED"]));
+
+        let mut game = new_game(GameMode::Marathon);
+        let ending = (0..100).find_map(|_| {
+            game.apply(Action::HardDrop);
+            game.compute_next_frame()
+        });
+        assert!(matches!(ending, Some(Ending::TopOut(_))));
+    }
+
+    #[test]
+    fn puzzles_end_on_their_goal_or_their_last_piece() {
+        let puzzle = Puzzle::bundled().remove(0);
+        let replay = Replay {
+            puzzle: Some(puzzle),
+            ..Replay::new(GameMode::Puzzle, 1, 1, 10, 20)
+        };
+
+        // the I piece down the well clears the 4 rows
+        let mut game = GameState::new(&replay);
+        assert_eq!(game.pieces_left(), 1);
+        game.apply(Action::RotateCw);
+        for _ in 0..10 {
+            game.apply(Action::MoveRight);
+        }
+        game.apply(Action::HardDrop);
+        assert!(matches!(game.compute_next_frame(), Some(Ending::Finished)));
+
+        let mut game = GameState::new(&replay);
+        game.apply(Action::HardDrop);
+        assert!(matches!(game.compute_next_frame(), Some(Ending::Failed)));
+    }
+}
@@ -0,0 +1,301 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{tspin::Spin, TICKS_PER_SECOND};
+
+/// guideline base score of a 0..=4 lines clear (multiplied by the level)
+const LINE_CLEAR_SCORES: [usize; 5] = [0, 100, 300, 500, 800];
+/// guideline base score of a T-spin clearing 0..=3 lines
+const T_SPIN_SCORES: [usize; 4] = [400, 800, 1200, 1600];
+/// same for a mini T-spin clearing 0..=2 lines
+const MINI_T_SPIN_SCORES: [usize; 3] = [100, 200, 400];
+/// NES score of a 0..=4 lines clear (multiplied by the level plus one)
+const NES_LINE_CLEAR_SCORES: [usize; 5] = [0, 40, 100, 300, 1200];
+const COMBO_SCORE: usize = 50;
+const LINES_PER_LEVEL: usize = 10;
+/// lines between two split times
+pub const SPLIT_LINES: usize = 10;
+/// last level of Master, reaching it ends the game
+pub const MASTER_MAX_LEVEL: usize = 999;
+
+/// how locks are scored and how the level goes up
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scoring {
+    /// guideline scores multiplied by the level, the level goes up every 10 lines
+    Guideline,
+    /// TGM scores, the level goes up by one per piece and one per line
+    Master,
+    /// NES scores, the level goes up every 10 lines once the lines of the starting level are done
+    Nes,
+}
+
+impl Scoring {
+    /// level of a game started without choosing one
+    pub fn first_level(&self) -> usize {
+        match self {
+            Scoring::Guideline => 1,
+            Scoring::Master | Scoring::Nes => 0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Stats {
+    pub scoring: Scoring,
+    pub score: usize,
+    pub level: usize,
+    pub start_level: usize,
+    pub lines: usize,
+    pub pieces: usize,
+    pub t_spins: usize,
+    pub mini_t_spins: usize,
+    /// inputs spent on top of the fewest needed, over all the pieces
+    pub finesse_faults: usize,
+    /// game time every `SPLIT_LINES` lines
+    pub splits: Vec<Duration>,
+    /// number of consecutive line clears minus one, `None` when the chain is broken
+    pub combo: Option<usize>,
+    /// whether the last line clear was a "difficult" one (tetris), next one will get the bonus
+    pub b2b: bool,
+    /// TGM combo multiplier, grows with the lines of consecutive clears (Master only)
+    pub master_combo: usize,
+    /// rows the falling piece was dropped by, scored with its line clear (Master only)
+    pub drop_rows: usize,
+    /// ticks the game ran for, pauses and menus don't tick
+    pub ticks: usize,
+}
+
+impl Stats {
+    pub fn new(start_level: usize, scoring: Scoring) -> Self {
+        Self {
+            scoring,
+            score: 0,
+            level: start_level,
+            start_level,
+            lines: 0,
+            pieces: 0,
+            t_spins: 0,
+            mini_t_spins: 0,
+            finesse_faults: 0,
+            splits: vec![],
+            combo: None,
+            b2b: false,
+            master_combo: 1,
+            drop_rows: 0,
+            ticks: 0,
+        }
+    }
+
+    /// soft drops score 1 point per row, hard drops 2 (nothing on the NES, which has none).
+    /// In Master they count when lines are cleared
+    pub fn on_drop(&mut self, rows: usize, hard: bool) {
+        match self.scoring {
+            Scoring::Guideline => self.score += if hard { 2 * rows } else { rows },
+            Scoring::Master => self.drop_rows += rows,
+            Scoring::Nes if !hard => self.score += rows,
+            Scoring::Nes => {}
+        }
+    }
+
+    /// updates the stats after a piece locked and cleared `cleared` lines, with a T-spin or not
+    pub fn on_lock(&mut self, cleared: usize, spin: Option<Spin>) {
+        self.pieces += 1;
+        match spin {
+            Some(Spin::Full) => self.t_spins += 1,
+            Some(Spin::Mini) => self.mini_t_spins += 1,
+            None => {}
+        }
+        match self.scoring {
+            Scoring::Guideline => self.score_guideline(cleared, spin),
+            Scoring::Master => self.score_master(cleared),
+            Scoring::Nes => self.score += NES_LINE_CLEAR_SCORES[cleared.min(4)] * (self.level + 1),
+        }
+        if cleared == 0 {
+            self.combo = None;
+            return;
+        }
+        self.combo = Some(self.combo.map_or(0, |c| c + 1));
+        self.lines += cleared;
+        while self.splits.len() < self.lines / SPLIT_LINES {
+            self.splits.push(self.elapsed());
+        }
+        self.level = match self.scoring {
+            Scoring::Guideline => self.start_level + self.lines / LINES_PER_LEVEL,
+            Scoring::Master => (self.level + cleared).min(MASTER_MAX_LEVEL),
+            Scoring::Nes => {
+                let start = self.start_level;
+                let first_level_up =
+                    (start * 10 + 10).min((start * 10).saturating_sub(50).max(100));
+                match self.lines.checked_sub(first_level_up) {
+                    Some(lines) => start + 1 + lines / LINES_PER_LEVEL,
+                    None => start,
+                }
+            }
+        };
+    }
+
+    fn score_guideline(&mut self, cleared: usize, spin: Option<Spin>) {
+        let base = match spin {
+            Some(Spin::Full) => T_SPIN_SCORES[cleared.min(3)],
+            Some(Spin::Mini) => MINI_T_SPIN_SCORES[cleared.min(2)],
+            None => LINE_CLEAR_SCORES[cleared.min(4)],
+        };
+        if cleared == 0 {
+            // a T-spin without lines still scores but doesn't keep the combo nor break the b2b
+            self.score += base * self.level;
+            return;
+        }
+
+        let combo = self.combo.map_or(0, |c| c + 1);
+        let difficult = cleared >= 4 || spin.is_some();
+
+        let mut score = base * self.level;
+        if difficult && self.b2b {
+            score = score * 3 / 2;
+        }
+        score += COMBO_SCORE * combo * self.level;
+
+        self.score += score;
+        self.b2b = difficult;
+    }
+
+    fn score_master(&mut self, cleared: usize) {
+        // every piece is worth a level, but the last level of a section (and the one before
+        // the end) only goes by with a line clear
+        if self.level % 100 != 99 && self.level < MASTER_MAX_LEVEL - 1 {
+            self.level += 1;
+        }
+        let drop_rows = std::mem::take(&mut self.drop_rows);
+        if cleared == 0 {
+            self.master_combo = 1;
+            return;
+        }
+        self.master_combo += 2 * cleared - 2;
+        self.score +=
+            ((self.level + cleared).div_ceil(4) + drop_rows) * cleared * self.master_combo;
+    }
+
+    /// game time, from the ticks so that it doesn't depend on the machine keeping up
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_millis((self.ticks * 1000 / TICKS_PER_SECOND) as u64)
+    }
+
+    /// pieces per second
+    pub fn pps(&self) -> f64 {
+        let secs = self.elapsed().as_secs_f64();
+        if secs <= 0.0 {
+            return 0.0;
+        }
+        self.pieces as f64 / secs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scores_back_to_backs_and_combos() {
+        let mut stats = Stats::new(1, Scoring::Guideline);
+        stats.on_lock(4, None);
+        assert_eq!(stats.score, 800);
+        // back to back tetris, 1 combo
+        stats.on_lock(4, None);
+        assert_eq!(stats.score, 800 + 1200 + 50);
+        // 2 combo, the back to back is broken
+        stats.on_lock(1, None);
+        assert_eq!(stats.score, 2050 + 100 + 100);
+        assert!(!stats.b2b);
+        assert_eq!((stats.lines, stats.level, stats.pieces), (9, 1, 3));
+
+        stats.on_lock(0, None);
+        assert_eq!(stats.combo, None);
+        stats.on_lock(2, None);
+        assert_eq!((stats.lines, stats.level), (11, 2));
+        // scored at the level the lines were cleared on
+        assert_eq!(stats.score, 2250 + 300);
+    }
+
+    #[test]
+    fn scores_t_spins() {
+        let mut stats = Stats::new(2, Scoring::Guideline);
+        stats.on_lock(2, Some(Spin::Full));
+        assert_eq!(stats.score, 1200 * 2);
+        // a mini without lines keeps the back to back
+        stats.on_lock(0, Some(Spin::Mini));
+        assert_eq!(stats.score, 2400 + 100 * 2);
+        assert!(stats.b2b);
+        stats.on_lock(3, Some(Spin::Full));
+        assert_eq!(stats.score, 2600 + 1600 * 2 * 3 / 2);
+        assert_eq!((stats.t_spins, stats.mini_t_spins), (2, 1));
+    }
+
+    #[test]
+    fn game_time_counts_ticks() {
+        let mut stats = Stats::new(1, Scoring::Guideline);
+        stats.ticks = 90 * TICKS_PER_SECOND + 3;
+        assert_eq!(stats.elapsed(), Duration::from_millis(90_050));
+        stats.pieces = 180;
+        assert!((stats.pps() - 180.0 / 90.05).abs() < 1e-9);
+        // split times are taken at the game time of the clear
+        stats.on_lock(4, None);
+        stats.ticks += 2 * TICKS_PER_SECOND;
+        stats.on_lock(4, None);
+        stats.on_lock(4, None);
+        assert_eq!(stats.splits, [Duration::from_millis(92_050)]);
+    }
+
+    #[test]
+    fn nes_scores_and_levels() {
+        let mut stats = Stats::new(0, Scoring::Nes);
+        stats.on_drop(5, false);
+        stats.on_drop(5, true);
+        stats.on_lock(4, None);
+        assert_eq!(stats.score, 5 + 1200);
+        stats.on_lock(4, None);
+        stats.on_lock(2, None);
+        assert_eq!(stats.score, 1205 + 1200 + 100);
+        // 10 lines from level 0
+        assert_eq!(stats.level, 1);
+
+        // from level 9, the first level up takes 100 lines, then 10 lines a level
+        let mut stats = Stats::new(9, Scoring::Nes);
+        stats.on_lock(1, None);
+        assert_eq!(stats.score, 40 * 10);
+        for _ in 0..24 {
+            stats.on_lock(4, None);
+        }
+        assert_eq!((stats.lines, stats.level), (97, 9));
+        stats.on_lock(3, None);
+        assert_eq!(stats.level, 10);
+        for _ in 0..5 {
+            stats.on_lock(2, None);
+        }
+        assert_eq!((stats.lines, stats.level), (110, 11));
+    }
+
+    #[test]
+    fn master_scores_and_levels() {
+        let mut stats = Stats::new(0, Scoring::Master);
+        stats.on_lock(0, None);
+        assert_eq!((stats.score, stats.level), (0, 1));
+
+        // level 2 once the piece locked, (ceil((2 + 1) / 4) + 5 dropped rows) * 1 line * combo 1
+        stats.on_drop(5, true);
+        stats.on_lock(1, None);
+        assert_eq!((stats.score, stats.level), (6, 3));
+        // combo 1 + 2 * 4 - 2, (ceil((4 + 4) / 4) + 0) * 4 * 7
+        stats.on_lock(4, None);
+        assert_eq!((stats.score, stats.level), (6 + 56, 8));
+
+        // the last level of a section needs a line clear
+        stats.level = 98;
+        stats.on_lock(0, None);
+        stats.on_lock(0, None);
+        assert_eq!(stats.level, 99);
+        stats.on_lock(1, None);
+        assert_eq!(stats.level, 100);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::utils::SGR;
+
+use super::{board::Block, types::TetrominoeType};
+
+/// colors of everything drawn on the board, the background always stays `BACKGROUD_COLOR`
+pub struct Palette {
+    pub box_color: SGR,
+    pub title: [SGR; 5],
+    /// piece colors, in `TetrominoeType` order
+    pub pieces: [SGR; 7],
+    pub ghost: SGR,
+    pub garbage: SGR,
+    /// cleared rows of the flash animation
+    pub flash: SGR,
+}
+
+impl Palette {
+    /// every piece of a type has the same color
+    pub fn piece(&self, ttype: TetrominoeType) -> SGR {
+        self.pieces[ttype as usize]
+    }
+
+    pub fn block(&self, block: Block) -> SGR {
+        match block {
+            Block::Piece(ttype) => self.piece(ttype),
+            Block::Garbage => self.garbage,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Theme {
+    #[default]
+    Default,
+    Bright,
+    Mono,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Default, Theme::Bright, Theme::Mono];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Default => "default",
+            Theme::Bright => "bright",
+            Theme::Mono => "mono",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Theme::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    pub fn palette(&self) -> Palette {
+        match self {
+            Theme::Default => Palette {
+                box_color: SGR::YellowBG,
+                title: [
+                    SGR::BlueBG,
+                    SGR::CyanBG,
+                    SGR::GreenBG,
+                    SGR::MagentaBG,
+                    SGR::RedBG,
+                ],
+                pieces: [
+                    SGR::CyanBG,
+                    SGR::YellowBG,
+                    SGR::MagentaBG,
+                    SGR::BlueBG,
+                    SGR::BrightRedBG,
+                    SGR::GreenBG,
+                    SGR::RedBG,
+                ],
+                ghost: SGR::BrightBlackBG,
+                garbage: SGR::WhiteBG,
+                flash: SGR::BrightWhiteBG,
+            },
+            Theme::Bright => Palette {
+                box_color: SGR::BrightWhiteBG,
+                title: [
+                    SGR::BrightBlueBG,
+                    SGR::BrightCyanBG,
+                    SGR::BrightGreenBG,
+                    SGR::BrightMagentaBG,
+                    SGR::BrightRedBG,
+                ],
+                pieces: [
+                    SGR::BrightCyanBG,
+                    SGR::BrightYellowBG,
+                    SGR::BrightMagentaBG,
+                    SGR::BrightBlueBG,
+                    SGR::YellowBG,
+                    SGR::BrightGreenBG,
+                    SGR::BrightRedBG,
+                ],
+                ghost: SGR::BrightBlackBG,
+                garbage: SGR::WhiteBG,
+                flash: SGR::WhiteBG,
+            },
+            Theme::Mono => Palette {
+                box_color: SGR::WhiteBG,
+                title: [SGR::WhiteBG; 5],
+                pieces: [SGR::BrightWhiteBG; 7],
+                ghost: SGR::BrightBlackBG,
+                garbage: SGR::WhiteBG,
+                flash: SGR::BrightWhiteBG,
+            },
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use crate::utils::Ivec2;
+
+use super::{
+    board::Board,
+    types::{LastMove, Tetrominoe, TetrominoeType},
+};
+
+/// index of the last SRS kick of a quarter turn (the one moving the piece by 1 and 2 rows), a
+/// mini done with it counts as a full T-spin
+const TST_KICK: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Spin {
+    Full,
+    Mini,
+}
+
+impl Spin {
+    /// callout shown for the spin, `cleared` being the number of cleared lines
+    pub fn callout(&self, cleared: usize) -> Vec<&'static str> {
+        let mut lines = match self {
+            Spin::Full => vec!["T-SPIN"],
+            Spin::Mini => vec!["MINI", "T-SPIN"],
+        };
+        match cleared {
+            1 => lines.push("SINGLE"),
+            2 => lines.push("DOUBLE"),
+            3 => lines.push("TRIPLE"),
+            _ => {}
+        }
+        lines
+    }
+}
+
+/// whether `piece`, about to lock on `board`, makes a T-spin: its last move has to be a rotation
+/// and 3 of the 4 corners around its center have to be taken (walls and floor included). It's a
+/// mini unless both corners on the pointing side are taken or the rotation used the TST kick
+pub fn detect(board: &Board, piece: &Tetrominoe) -> Option<Spin> {
+    let Some(LastMove::Rotation { kick, half_turn }) = piece.last_move else {
+        return None;
+    };
+    if piece.ttype != TetrominoeType::T {
+        return None;
+    }
+
+    // corners of the 3x3 square clockwise from the top left one, the center being (1, 1)
+    let corners =
+        [(0, 0), (2, 0), (2, 2), (0, 2)].map(|(x, y)| !board.is_free(piece.pos + Ivec2::new(x, y)));
+    if corners.iter().filter(|taken| **taken).count() < 3 {
+        return None;
+    }
+
+    // pointing up, the front corners are the top ones, then they turn with the piece
+    let front = [corners[piece.rotation], corners[(piece.rotation + 1) % 4]];
+    match front == [true, true] || (kick == TST_KICK && !half_turn) {
+        true => Some(Spin::Full),
+        false => Some(Spin::Mini),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::rules::Rotation;
+
+    /// a 10x20 board with `rows` at the bottom (`#` for blocks)
+    fn board(rows: &[&str]) -> Board {
+        let mut board = Board::new(10, 20);
+        let top = 40 - rows.len();
+        for (y, row) in rows.iter().enumerate() {
+            let blocks = row
+                .char_indices()
+                .filter(|(_, c)| *c == '#')
+                .map(|(x, _)| Ivec2::new(x as isize, (top + y) as isize))
+                .collect::<Vec<_>>();
+            board.lock(&blocks, TetrominoeType::O);
+        }
+        board
+    }
+
+    /// a T turned `rotation` times clockwise, its 3x3 square at `x`, `y`, last moved by `last_move`
+    fn t(rotation: usize, x: isize, y: isize, last_move: LastMove) -> Tetrominoe {
+        let mut t = Tetrominoe::new(TetrominoeType::T, Rotation::Srs);
+        for _ in 0..rotation {
+            t = t.rotated(false);
+        }
+        t.pos = Ivec2::new(x, y);
+        t.last_move = Some(last_move);
+        t
+    }
+
+    const TURNED: LastMove = LastMove::Rotation {
+        kick: 0,
+        half_turn: false,
+    };
+
+    #[test]
+    fn both_front_corners_make_a_full_t_spin() {
+        // pointing down into a T-spin double slot
+        let board = board(&["..#.......", "...#######", "#.########"]);
+        let piece = t(2, 0, 37, TURNED);
+        assert!(board.fits(&piece.cells()));
+        assert!(detect(&board, &piece) == Some(Spin::Full));
+    }
+
+    #[test]
+    fn one_front_corner_makes_a_mini() {
+        // pointing up, the top left corner and both bottom ones taken
+        let board = board(&["#.........", "...#######", "#.#######."]);
+        let piece = t(0, 0, 37, TURNED);
+        assert!(detect(&board, &piece) == Some(Spin::Mini));
+    }
+
+    #[test]
+    fn walls_count_as_corners() {
+        // pointing right against the left wall, only the bottom right corner is a block
+        let board = board(&["..........", "..........", ".#........"]);
+        let piece = t(1, -1, 37, TURNED);
+        assert!(board.fits(&piece.cells()));
+        assert!(detect(&board, &piece) == Some(Spin::Mini));
+    }
+
+    #[test]
+    fn the_tst_kick_upgrades_a_mini() {
+        let board = board(&["#.........", "...#######", "#.#######."]);
+        let tst = LastMove::Rotation {
+            kick: TST_KICK,
+            half_turn: false,
+        };
+        assert!(detect(&board, &t(0, 0, 37, tst)) == Some(Spin::Full));
+        // the 5th kick of a half turn isn't the TST one
+        let half_turn = LastMove::Rotation {
+            kick: TST_KICK,
+            half_turn: true,
+        };
+        assert!(detect(&board, &t(0, 0, 37, half_turn)) == Some(Spin::Mini));
+    }
+
+    #[test]
+    fn no_spin_without_rotation_nor_corners() {
+        let slot = board(&["..#.......", "...#######", "#.########"]);
+        assert!(detect(&slot, &t(2, 0, 37, LastMove::Shift)).is_none());
+
+        let mut s = t(2, 0, 37, TURNED);
+        s.ttype = TetrominoeType::S;
+        assert!(detect(&slot, &s).is_none());
+
+        // only the bottom corners
+        let flat = board(&["..........", "...#######", "#.########"]);
+        assert!(detect(&flat, &t(2, 0, 37, TURNED)).is_none());
+    }
+}
@@ -1,206 +1,260 @@
-use std::time::{Duration, Instant};
-
 use nanorand::{Rng, WyRand};
-use termsize::Size;
 
-use crate::utils::{Uvec2, SGR};
+use crate::utils::Ivec2;
+
+use super::rules::Rotation;
 
+#[derive(Clone)]
 pub struct Tetrominoe {
     pub ttype: TetrominoeType,
-    pub vertices_pos: Vec<Uvec2>,
-    pub still_time: Duration,
-    pub now: Instant,
-    pub color: SGR,
-    pub scale: usize,
-    inner_box_size: Size,
+    /// blocks relative to the top left corner of the piece's bounding square
+    pub blocks: [Ivec2; 4],
+    /// board position of the top left corner of the bounding square
+    pub pos: Ivec2,
+    /// orientation: 0 in spawn orientation, then one more per clockwise quarter turn
+    pub rotation: usize,
+    /// how the piece turns
+    pub rotation_system: Rotation,
+    /// part of a row fallen since the last fall, in 1/`Gravity::ticks` rows
+    pub fall: usize,
+    /// ticks spent on the ground since the lock delay was last reset
+    pub lock_ticks: usize,
+    /// number of times moving reset the lock delay since the piece reached `lowest`
+    pub lock_resets: usize,
+    /// lowest row reached by the bottom of the piece
+    pub lowest: isize,
+    /// last successful move, `None` right after spawning
+    pub last_move: Option<LastMove>,
 }
 
-impl Tetrominoe {
-    pub fn new(
-        inner_box_size: &Size,
-        scale: usize,
-        ttype: Option<TetrominoeType>,
-        pos: Option<Uvec2>,
-    ) -> Self {
-        let mut rng = WyRand::new();
-
-        let ttype = ttype.unwrap_or(TetrominoeType::random());
-        let (x, y) = match pos {
-            Some(vec) => (vec.x, vec.y),
-            None => {
-                let (mut x, y) = (
-                    rng.generate_range(
-                        2_usize * scale..=(inner_box_size.cols as usize - 2 * scale),
-                    ),
-                    1,
-                );
-                x -= x % scale;
-
-                (x, y)
-            }
-        };
-
-        let mut vertices_pos = Vec::with_capacity(4);
-        vertices_pos.push(Uvec2::new(x, y));
-        match ttype {
-            TetrominoeType::Bar => {
-                for i in 1..4 {
-                    vertices_pos.push(Uvec2::new(x, y + i * scale))
-                }
-            }
-            TetrominoeType::Square => {
-                vertices_pos.push(Uvec2::new(x + scale, y));
-                vertices_pos.push(Uvec2::new(x, y + scale));
-                vertices_pos.push(Uvec2::new(x + scale, y + scale));
-            }
-            TetrominoeType::Pyramid => {
-                vertices_pos.push(Uvec2::new(x - scale, y));
-                vertices_pos.push(Uvec2::new(x + scale, y));
-                vertices_pos.push(Uvec2::new(x, y + scale));
-            }
-            TetrominoeType::LLeft => {
-                for i in 1..3 {
-                    vertices_pos.push(Uvec2::new(x, y + i * scale))
-                }
-                vertices_pos.push(Uvec2::new(x - scale, y + 2 * scale))
-            }
-            TetrominoeType::LRight => {
-                for i in 1..3 {
-                    vertices_pos.push(Uvec2::new(x, y + i * scale))
-                }
-                vertices_pos.push(Uvec2::new(x + scale, y + 2 * scale))
-            }
-            TetrominoeType::SnakeLeft => {
-                vertices_pos.push(Uvec2::new(x - scale, y));
-                vertices_pos.push(Uvec2::new(x, y + scale));
-                vertices_pos.push(Uvec2::new(x + scale, y + scale));
-            }
-            TetrominoeType::SnakeRight => {
-                vertices_pos.push(Uvec2::new(x + scale, y));
-                vertices_pos.push(Uvec2::new(x, y + scale));
-                vertices_pos.push(Uvec2::new(x - scale, y + scale));
-            }
-        };
+/// how a piece moved, T-spins have to end with a rotation
+#[derive(Clone, Copy, PartialEq)]
+pub enum LastMove {
+    /// sideways or down
+    Shift,
+    /// `kick` is the index of the kick that made it fit, 0 when it turned in place
+    Rotation { kick: usize, half_turn: bool },
+}
 
+impl Tetrominoe {
+    /// a piece in its spawn orientation, not on the board yet (see `GameState::spawn`)
+    pub fn new(ttype: TetrominoeType, rotation_system: Rotation) -> Self {
         Self {
             ttype,
-            vertices_pos,
-            still_time: Duration::from_millis(50), // should vary according to score
-            color: [
-                SGR::BlueBG,
-                SGR::CyanBG,
-                SGR::GreenBG,
-                SGR::MagentaBG,
-                SGR::RedBG,
-            ][rng.generate_range(0_usize..5)],
-            now: Instant::now(),
-            scale,
-            inner_box_size: Size {
-                rows: inner_box_size.rows,
-                cols: inner_box_size.cols,
-            },
+            blocks: ttype.shape(rotation_system),
+            pos: Ivec2::new(0, 0),
+            rotation: 0,
+            rotation_system,
+            fall: 0,
+            lock_ticks: 0,
+            lock_resets: 0,
+            lowest: isize::MIN,
+            last_move: None,
         }
     }
 
-    pub fn from_self(rhs: &Self, scale: Option<usize>, pos: Option<Uvec2>) -> Self {
-        let mut s = Self::new(
-            &rhs.inner_box_size,
-            scale.unwrap_or(rhs.scale),
-            Some(rhs.ttype),
-            pos,
-        );
-        s.still_time = rhs.still_time;
-        s.color = rhs.color;
-        s
+    /// back to the spawn orientation, when the piece goes to the hold
+    pub fn reset(&mut self) {
+        self.blocks = self.ttype.shape(self.rotation_system);
+        self.rotation = 0;
+        self.fall = 0;
+        self.lock_ticks = 0;
+        self.lock_resets = 0;
+        self.lowest = isize::MIN;
+        self.last_move = None;
     }
 
-    pub fn rotate(&mut self, ccw: bool) {
-        let cp = self.vertices_pos[0];
+    /// row of the lowest block
+    pub fn bottom(&self) -> isize {
+        self.pos.y + self.bounds().3
+    }
 
-        let mut rvp = Vec::with_capacity(4);
-        rvp.push(cp);
+    /// board positions of the blocks
+    pub fn cells(&self) -> [Ivec2; 4] {
+        self.blocks.map(|b| b + self.pos)
+    }
 
-        for vp in self.vertices_pos.iter().skip(1) {
-            let (ox, oy) = (cp.x as isize - vp.x as isize, cp.y as isize - vp.y as isize);
-            let (rox, roy) = match ccw {
-                true => (oy, -ox),
-                false => (-oy, ox),
-            };
+    /// the piece moved by (`dx`, `dy`) blocks
+    pub fn moved(&self, dx: isize, dy: isize) -> Self {
+        let mut moved = self.clone();
+        moved.pos = moved.pos + Ivec2::new(dx, dy);
+        moved
+    }
 
-            let (rvpx, rvpy) = (cp.x as isize + rox, cp.y as isize + roy);
-            if rvpx >= 2 * self.scale as isize
-                && rvpx <= self.inner_box_size.cols as isize - 2 * self.scale as isize
-                && rvpy >= 1
-                && rvpy <= self.inner_box_size.rows as isize - 2 * self.scale as isize
-            {
-                rvp.push(Uvec2::new(rvpx as usize, rvpy as usize));
-            } else {
-                break;
-            }
-        }
-        if rvp.len() == 4 {
-            self.vertices_pos = rvp; // update only if all vertices can rotate
-        }
+    /// the piece rotated by a quarter turn inside its bounding square, which gives the SRS
+    /// orientations (the O piece doesn't move)
+    pub fn rotated(&self, ccw: bool) -> Self {
+        let n = self.ttype.box_size() - 1;
+        let mut rotated = self.clone();
+        // y goes down, so (x, y) -> (n - y, x) turns clockwise
+        rotated.blocks = self.blocks.map(|Ivec2 { x, y }| match ccw {
+            true => Ivec2::new(y, n - x),
+            false => Ivec2::new(n - y, x),
+        });
+        rotated.rotation = (self.rotation + if ccw { 3 } else { 1 }) % 4;
+        rotated.flip_two_sided();
+        rotated
     }
 
-    pub fn fall(&mut self) {
-        for vp in self.vertices_pos.iter_mut() {
-            vp.y += 1
-        }
+    /// the piece turned upside down inside its bounding square
+    pub fn rotated_180(&self) -> Self {
+        let n = self.ttype.box_size() - 1;
+        let mut rotated = self.clone();
+        rotated.blocks = self.blocks.map(|Ivec2 { x, y }| Ivec2::new(n - x, n - y));
+        rotated.rotation = (self.rotation + 2) % 4;
+        rotated.flip_two_sided();
+        rotated
     }
 
-    pub fn translate_right(&mut self) {
-        for vp in &self.vertices_pos {
-            if vp.x + self.scale > self.inner_box_size.cols as usize - self.scale {
-                return;
-            }
-        }
-        for vp in self.vertices_pos.iter_mut() {
-            vp.x += self.scale
+    /// pieces with only two orientations go back and forth between them instead of turning
+    fn flip_two_sided(&mut self) {
+        if let Some(flipped) = self.ttype.flipped_shape(self.rotation_system) {
+            self.blocks = match self.rotation % 2 {
+                0 => self.ttype.shape(self.rotation_system),
+                _ => flipped,
+            };
         }
     }
-    pub fn translate_left(&mut self) {
-        for vp in &self.vertices_pos {
-            if vp.x + self.scale < 2_usize * self.scale {
-                return;
-            }
-        }
-        for vp in self.vertices_pos.iter_mut() {
-            vp.x -= self.scale
-        }
+
+    /// leftmost, rightmost, top and bottom block offsets
+    pub fn bounds(&self) -> (isize, isize, isize, isize) {
+        let xs = self.blocks.map(|b| b.x);
+        let ys = self.blocks.map(|b| b.y);
+        (
+            *xs.iter().min().unwrap_or(&0),
+            *xs.iter().max().unwrap_or(&0),
+            *ys.iter().min().unwrap_or(&0),
+            *ys.iter().max().unwrap_or(&0),
+        )
     }
 }
 
+/// the seven standard pieces, in the order of `Palette::pieces`
 #[repr(u8)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum TetrominoeType {
-    Bar,
-    Square,
-    Pyramid,
-    LLeft,
-    LRight,
-    SnakeLeft,
-    SnakeRight,
+    I,
+    O,
+    T,
+    J,
+    L,
+    S,
+    Z,
 }
 
 impl TetrominoeType {
-    pub fn random() -> Self {
-        let mut rng = WyRand::new();
+    pub const ALL: [TetrominoeType; 7] = [
+        TetrominoeType::I,
+        TetrominoeType::O,
+        TetrominoeType::T,
+        TetrominoeType::J,
+        TetrominoeType::L,
+        TetrominoeType::S,
+        TetrominoeType::Z,
+    ];
+
+    /// letter of the piece in puzzle files
+    pub fn letter(&self) -> char {
+        b"IOTJLSZ"[*self as usize] as char
+    }
+
+    /// case insensitive
+    pub fn from_letter(letter: char) -> Option<Self> {
+        TetrominoeType::ALL
+            .into_iter()
+            .find(|t| t.letter() == letter.to_ascii_uppercase())
+    }
+
+    pub fn random(rng: &mut WyRand) -> Self {
         rng.generate_range(0_u8..=6).into()
     }
+
+    /// side of the square the piece rotates in
+    pub fn box_size(&self) -> isize {
+        match self {
+            TetrominoeType::I => 4,
+            TetrominoeType::O => 2,
+            _ => 3,
+        }
+    }
+
+    /// blocks in spawn orientation, relative to the top left corner of the bounding square.
+    /// SRS pieces spawn flat side down (pointing up), Nintendo ones flat side up, one row lower
+    pub fn shape(&self, rotation: Rotation) -> [Ivec2; 4] {
+        let blocks = match (rotation, self) {
+            (Rotation::Srs, TetrominoeType::I) => [(0, 1), (1, 1), (2, 1), (3, 1)],
+            (Rotation::Srs, TetrominoeType::T) => [(1, 0), (0, 1), (1, 1), (2, 1)],
+            (Rotation::Srs, TetrominoeType::J) => [(0, 0), (0, 1), (1, 1), (2, 1)],
+            (Rotation::Srs, TetrominoeType::L) => [(2, 0), (0, 1), (1, 1), (2, 1)],
+            (Rotation::Srs, TetrominoeType::S) => [(1, 0), (2, 0), (0, 1), (1, 1)],
+            (Rotation::Srs, TetrominoeType::Z) => [(0, 0), (1, 0), (1, 1), (2, 1)],
+            (Rotation::Nintendo, TetrominoeType::I) => [(0, 2), (1, 2), (2, 2), (3, 2)],
+            (Rotation::Nintendo, TetrominoeType::T) => [(0, 1), (1, 1), (2, 1), (1, 2)],
+            (Rotation::Nintendo, TetrominoeType::J) => [(0, 1), (1, 1), (2, 1), (2, 2)],
+            (Rotation::Nintendo, TetrominoeType::L) => [(0, 1), (1, 1), (2, 1), (0, 2)],
+            (Rotation::Nintendo, TetrominoeType::S) => [(1, 1), (2, 1), (0, 2), (1, 2)],
+            (Rotation::Nintendo, TetrominoeType::Z) => [(0, 1), (1, 1), (1, 2), (2, 2)],
+            (_, TetrominoeType::O) => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        };
+        blocks.map(|(x, y)| Ivec2::new(x, y))
+    }
+
+    /// the other orientation of the pieces that only have two, standing on the right side of
+    /// the square (right-handed Nintendo rotation)
+    fn flipped_shape(&self, rotation: Rotation) -> Option<[Ivec2; 4]> {
+        let blocks = match (rotation, self) {
+            (Rotation::Nintendo, TetrominoeType::I) => [(2, 0), (2, 1), (2, 2), (2, 3)],
+            (Rotation::Nintendo, TetrominoeType::S) => [(1, 0), (1, 1), (2, 1), (2, 2)],
+            (Rotation::Nintendo, TetrominoeType::Z) => [(2, 0), (1, 1), (2, 1), (1, 2)],
+            _ => return None,
+        };
+        Some(blocks.map(|(x, y)| Ivec2::new(x, y)))
+    }
 }
 
 impl From<u8> for TetrominoeType {
     fn from(id: u8) -> Self {
-        match id {
-            0 => TetrominoeType::Bar,
-            1 => TetrominoeType::Square,
-            2 => TetrominoeType::Pyramid,
-            3 => TetrominoeType::LLeft,
-            4 => TetrominoeType::LRight,
-            5 => TetrominoeType::SnakeLeft,
-            6 => TetrominoeType::SnakeRight,
-            _ => unreachable!(),
+        TetrominoeType::ALL[id as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::theme::Theme;
+
+    #[test]
+    fn four_quarter_turns_give_the_piece_back() {
+        for ttype in TetrominoeType::ALL {
+            let piece = Tetrominoe::new(ttype, Rotation::Srs);
+            let mut turned = piece.clone();
+            for _ in 0..4 {
+                turned = turned.rotated(false);
+            }
+            assert_eq!(turned.blocks, piece.blocks, "{ttype:?}");
+            assert_eq!(piece.rotated(false).rotated(true).blocks, piece.blocks);
+        }
+    }
+
+    #[test]
+    fn pieces_spawn_flat_side_down() {
+        let t = Tetrominoe::new(TetrominoeType::T, Rotation::Srs);
+        assert_eq!(t.bounds(), (0, 2, 0, 1));
+        // pointing right after a clockwise turn
+        let turned = t.rotated(false);
+        assert!(turned.blocks.contains(&Ivec2::new(2, 1)));
+        assert_eq!(turned.bounds(), (1, 2, 0, 2));
+    }
+
+    #[test]
+    fn every_type_has_its_color() {
+        for theme in [Theme::Default, Theme::Bright] {
+            let palette = theme.palette();
+            for (i, a) in TetrominoeType::ALL.iter().enumerate() {
+                for b in &TetrominoeType::ALL[i + 1..] {
+                    assert!(palette.piece(*a) != palette.piece(*b), "{a:?} {b:?}");
+                }
+            }
         }
     }
 }
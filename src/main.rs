@@ -1,39 +1,158 @@
-use std::{io::IsTerminal, panic, thread, time::Duration};
+use std::{io::IsTerminal, panic, path::PathBuf};
 
-use game::GameManager;
+use cli::{Cli, Command};
+use game::{
+    mode::GameMode,
+    puzzle::Puzzle,
+    replay::Replay,
+    rules::Rules,
+    scores::{HighScores, ScoreEntry},
+    settings::Settings,
+    GameManager, Launch, Start,
+};
 use utils::SGR;
 
+mod cli;
 mod game;
 mod utils;
 
+/// `scores` command: the score tables of `mode` (every mode if `None`), one per rule set
+fn print_scores(mode: Option<GameMode>) {
+    let high_scores = HighScores::load();
+    let modes = match mode {
+        Some(mode) => vec![mode],
+        None => GameMode::ALL.to_vec(),
+    };
+    for mode in modes {
+        let rule_sets = mode.rules().map_or(Rules::ALL.to_vec(), |r| vec![r]);
+        for rules in rule_sets {
+            println!("{} ({})", mode.name(), rules.name());
+            println!("{}", ScoreEntry::table_header());
+            let top = high_scores.top(mode, rules);
+            if top.is_empty() {
+                println!("no score yet");
+            }
+            for (i, entry) in top.iter().enumerate() {
+                println!("{}", entry.table_row(i + 1));
+            }
+            println!();
+        }
+    }
+}
+
+/// sets the terminal up and plays until the player quits
+fn run(settings: Settings, config_path: Option<PathBuf>, launch: Launch) {
+    let mut gm = match GameManager::init(settings, config_path, launch) {
+        Ok(gm) => gm,
+        Err(why) => {
+            cprintln!(why, SGR::RedFG);
+            std::process::exit(1)
+        }
+    };
+    if let Err(why) = gm.start() {
+        cprintln!(format!("Game crashed: {why:?}"), SGR::RedFG)
+    }
+}
+
 fn main() {
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(why) => {
+            cprintln!(
+                format!("error: {why}\nsee `{} --help`", env!("CARGO_PKG_NAME")),
+                SGR::RedFG
+            );
+            std::process::exit(2)
+        }
+    };
+    if cli.help {
+        print!("{}", cli::usage());
+        return;
+    }
+    if cli.version {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        return;
+    }
+    if let Some(Command::Scores) = cli.command {
+        print_scores(cli.mode);
+        return;
+    }
+
     if !std::io::stdout().is_terminal() {
         cprintln!("Please run this inside a modern terminal", SGR::RedFG);
         std::process::exit(1)
     }
 
-    let is_safe_mode = std::env::args().skip(1).any(|a| a == "-s" || a == "--safe");
-    if is_safe_mode {
+    let config_path = cli.config.clone().or_else(Settings::default_path);
+    let loaded = match &config_path {
+        Some(path) => Settings::load(path),
+        None => Ok(Settings::default()),
+    };
+    let settings = match loaded {
+        Ok(settings) => settings,
+        Err(why) => {
+            cprintln!(why, SGR::RedFG);
+            std::process::exit(1)
+        }
+    };
+
+    let mode = cli.mode.unwrap_or(GameMode::Marathon);
+    let (mut width, mut height) = (cli.width, cli.height);
+    let puzzle = cli.puzzle.as_ref().map(|path| match Puzzle::load(path) {
+        Ok(puzzle) => {
+            // as wide as the puzzle, and high enough
+            width = Some(puzzle.width());
+            height = Some(height.unwrap_or(settings.height).max(puzzle.min_height()));
+            puzzle
+        }
+        Err(why) => {
+            cprintln!(why, SGR::RedFG);
+            std::process::exit(1)
+        }
+    });
+    let start = match &cli.command {
+        Some(Command::Play) => Some(Start::Play(mode)),
+        Some(Command::Bot) => Some(Start::Bot(mode)),
+        Some(Command::Replay(path)) => match Replay::load(path) {
+            Ok(replay) => {
+                // replays only play out the same on the board they were recorded on
+                (width, height) = (Some(replay.width), Some(replay.height));
+                Some(Start::Replay(Box::new(replay)))
+            }
+            Err(why) => {
+                cprintln!(why, SGR::RedFG);
+                std::process::exit(1)
+            }
+        },
+        Some(Command::Scores) | None => None,
+    };
+    let launch = Launch {
+        start,
+        mode: cli.mode,
+        seed: cli.seed,
+        level: cli.level,
+        puzzle,
+        width,
+        height,
+        record: cli.record,
+        theme: cli.theme,
+        // https://no-color.org
+        no_color: cli.no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()),
+    };
+
+    if cli.safe {
         // to detect if a panic occured, if yes recover from it since we must give the control of the terminal back to the user
         println!("Game launching in safe mode...");
         // thread::sleep(Duration::from_secs(1));
-        let game_exiting_result = panic::catch_unwind(|| {
-            let mut gm = GameManager::init();
-            if let Err(why) = gm.start() {
-                cprintln!(format!("Game crashed: {why:?}"), SGR::RedFG)
-            }
-        });
+        let game_exiting_result = panic::catch_unwind(|| run(settings, config_path, launch));
         if game_exiting_result.is_err() {
-            print!("\x1b[2J\x1b[3J\x1b[?25h"); // clear screen and show cursor
+            print!("\x1b[2J\x1b[3J\x1b[?1004l\x1b[?25h"); // clear screen, stop focus reporting and show cursor
             cprintln!(
                 format!("Fatal game crash: recovered from main. Clearing screen"),
                 SGR::RedFG
             )
         }
     } else {
-        let mut gm = GameManager::init();
-        if let Err(why) = gm.start() {
-            cprintln!(format!("Game crashed: {why:?}"), SGR::RedFG)
-        }
+        run(settings, config_path, launch)
     }
 }
@@ -1,10 +1,10 @@
 use std::{
     fmt::Display,
-    ops::{Add, Sub},
+    ops::Add,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use termsize::Size;
-
 #[macro_export]
 macro_rules! cprintln {
     ($msg:expr, $c:expr) => {
@@ -21,6 +21,7 @@ pub enum SGR {
     Light,
     Italic,
     Underline,
+    Reverse = 7,
     Strike = 9,
 
     BlackFG = 30,
@@ -94,3 +95,73 @@ impl Add for Uvec2 {
         }
     }
 }
+
+/// signed position, for blocks that can be out of the board while moving
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Ivec2 {
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Ivec2 {
+    pub fn new(x: isize, y: isize) -> Self {
+        Self { x, y }
+    }
+}
+
+impl Add for Ivec2 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+        }
+    }
+}
+
+/// directory where the game stores its data (`$XDG_DATA_HOME/corrodis`, defaults to `~/.local/share/corrodis`)
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("corrodis"))
+}
+
+/// directory of the config file (`$XDG_CONFIG_HOME/corrodis`, defaults to `~/.config/corrodis`)
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("corrodis"))
+}
+
+/// seconds since the unix epoch
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// formats a duration as `mm:ss.cc`
+pub fn fmt_duration(d: Duration) -> String {
+    format!(
+        "{:02}:{:02}.{:02}",
+        d.as_secs() / 60,
+        d.as_secs() % 60,
+        d.subsec_millis() / 10
+    )
+}
+
+/// formats a duration as `mm:ss.mmm`, for the modes played against the clock
+pub fn fmt_duration_ms(d: Duration) -> String {
+    format!(
+        "{:02}:{:02}.{:03}",
+        d.as_secs() / 60,
+        d.as_secs() % 60,
+        d.subsec_millis()
+    )
+}